@@ -0,0 +1,171 @@
+use chrono::TimeDelta;
+
+use crate::registry::{
+    MAX_CUSTOM_FREQUENCY_MINUTES, MIN_CUSTOM_FREQUENCY_MINUTES, ReminderFrequency,
+};
+
+/// Share of the current interval added or removed on each adjustment
+pub const ADAPTIVE_STEP_PERCENT: u32 = 20;
+
+/// Acknowledging a reminder within this delay makes the interval grow
+pub fn quick_acknowledgement_delay() -> TimeDelta {
+    TimeDelta::minutes(10)
+}
+
+/// User-chosen bounds, in minutes, of an adaptive reminder interval
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptiveBounds {
+    pub min_minutes: u32,
+    pub max_minutes: u32,
+}
+
+impl AdaptiveBounds {
+    /// Rejects bounds outside of the custom frequency limits, or in the wrong order
+    pub fn new(min_minutes: u32, max_minutes: u32) -> Option<Self> {
+        let allowed = MIN_CUSTOM_FREQUENCY_MINUTES..=MAX_CUSTOM_FREQUENCY_MINUTES;
+        (allowed.contains(&min_minutes)
+            && allowed.contains(&max_minutes)
+            && min_minutes <= max_minutes)
+            .then_some(Self {
+                min_minutes,
+                max_minutes,
+            })
+    }
+
+    pub fn clamp(&self, minutes: u32) -> u32 {
+        minutes.clamp(self.min_minutes, self.max_minutes)
+    }
+
+    /// Brings the frequency within the bounds, leaving it as is when it already is
+    pub fn clamp_frequency(&self, frequency: ReminderFrequency) -> ReminderFrequency {
+        let minutes = frequency.minutes();
+        match self.clamp(minutes) {
+            clamped if clamped == minutes => frequency,
+            clamped => ReminderFrequency::Custom(clamped),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdjustmentReason {
+    QuickAcknowledgement,
+    Ignored,
+}
+
+impl AdjustmentReason {
+    pub fn to_db(self) -> &'static str {
+        match self {
+            AdjustmentReason::QuickAcknowledgement => "QuickAcknowledgement",
+            AdjustmentReason::Ignored => "Ignored",
+        }
+    }
+
+    pub fn from_db(reason: &str) -> Option<Self> {
+        match reason {
+            "QuickAcknowledgement" => Some(AdjustmentReason::QuickAcknowledgement),
+            "Ignored" => Some(AdjustmentReason::Ignored),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for AdjustmentReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdjustmentReason::QuickAcknowledgement => write!(
+                f,
+                "rappel bu en moins de {} min",
+                quick_acknowledgement_delay().num_minutes()
+            ),
+            AdjustmentReason::Ignored => write!(f, "rappel précédent ignoré"),
+        }
+    }
+}
+
+/// Grows the interval after a quick acknowledgement and shrinks it after an ignored reminder
+pub fn adjusted_minutes(current: u32, bounds: AdaptiveBounds, reason: AdjustmentReason) -> u32 {
+    let step = (current * ADAPTIVE_STEP_PERCENT / 100).max(1);
+    match reason {
+        AdjustmentReason::QuickAcknowledgement => bounds.clamp(current + step),
+        AdjustmentReason::Ignored => bounds.clamp(current.saturating_sub(step)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(min_minutes: u32, max_minutes: u32) -> AdaptiveBounds {
+        AdaptiveBounds::new(min_minutes, max_minutes).unwrap()
+    }
+
+    #[test]
+    fn intervals_move_by_a_fifth() {
+        use AdjustmentReason::{Ignored, QuickAcknowledgement};
+        let bounds = bounds(10, 480);
+        for (current, reason, expected) in [
+            (60, QuickAcknowledgement, 72),
+            (60, Ignored, 48),
+            (100, QuickAcknowledgement, 120),
+            (100, Ignored, 80),
+            // The step is at least a minute
+            (12, QuickAcknowledgement, 14),
+            (14, Ignored, 12),
+        ] {
+            assert_eq!(
+                adjusted_minutes(current, bounds, reason),
+                expected,
+                "{current} min, {reason:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn intervals_stay_within_the_bounds() {
+        use AdjustmentReason::{Ignored, QuickAcknowledgement};
+        let bounds = bounds(30, 90);
+        for (current, reason, expected) in [
+            (80, QuickAcknowledgement, 90),
+            (90, QuickAcknowledgement, 90),
+            (35, Ignored, 30),
+            (30, Ignored, 30),
+            // An interval already outside the bounds is brought back within them
+            (120, Ignored, 90),
+            (20, QuickAcknowledgement, 30),
+        ] {
+            assert_eq!(
+                adjusted_minutes(current, bounds, reason),
+                expected,
+                "{current} min, {reason:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn bounds_must_be_allowed_and_ordered() {
+        assert!(
+            AdaptiveBounds::new(MIN_CUSTOM_FREQUENCY_MINUTES, MAX_CUSTOM_FREQUENCY_MINUTES)
+                .is_some()
+        );
+        assert!(AdaptiveBounds::new(MIN_CUSTOM_FREQUENCY_MINUTES - 1, 60).is_none());
+        assert!(AdaptiveBounds::new(60, MAX_CUSTOM_FREQUENCY_MINUTES + 1).is_none());
+        assert!(AdaptiveBounds::new(90, 30).is_none());
+    }
+
+    #[test]
+    fn frequencies_are_brought_within_the_bounds() {
+        let bounds = bounds(30, 90);
+        assert_eq!(
+            bounds.clamp_frequency(ReminderFrequency::ThreeHours),
+            ReminderFrequency::Custom(90)
+        );
+        assert_eq!(
+            bounds.clamp_frequency(ReminderFrequency::Custom(15)),
+            ReminderFrequency::Custom(30)
+        );
+        assert_eq!(
+            bounds.clamp_frequency(ReminderFrequency::OneHour),
+            ReminderFrequency::OneHour
+        );
+    }
+}
@@ -1 +1,2 @@
 pub(crate) mod register_buttons;
+pub(crate) mod reminder_buttons;
@@ -46,6 +46,14 @@ impl Display for ReminderFrequency {
             ReminderFrequency::ThirtyMin => write!(f, "30 min"),
             ReminderFrequency::OneHour => write!(f, "1 heure"),
             ReminderFrequency::ThreeHours => write!(f, "3 heures"),
+            ReminderFrequency::Custom(minutes) if *minutes < 60 => write!(f, "{minutes} min"),
+            ReminderFrequency::Custom(minutes) if minutes % 60 == 0 => match minutes / 60 {
+                1 => write!(f, "1 heure"),
+                hours => write!(f, "{hours} heures"),
+            },
+            ReminderFrequency::Custom(minutes) => {
+                write!(f, "{}h{:02}", minutes / 60, minutes % 60)
+            }
         }
     }
 }
@@ -0,0 +1,194 @@
+use chrono::NaiveDateTime;
+use chrono::TimeDelta;
+use serenity::all::{
+    ButtonStyle, ComponentInteraction, ComponentInteractionDataKind, Context, CreateButton,
+    CreateInteractionResponse, CreateInteractionResponseMessage, CreateSelectMenu,
+    CreateSelectMenuKind, CreateSelectMenuOption, User,
+};
+use tracing::info;
+
+use crate::{
+    registry::{
+        UserSettings, acknowledge_user_reminder, lookup_last_reminded, lookup_user_settings,
+        record_user_intake, snooze_user_reminder,
+    },
+    schedule::{next_active_time, next_reminder_time},
+};
+
+pub const BUTTON_SNOOZE_10_MIN_ID: &str = "snooze10min";
+pub const BUTTON_SNOOZE_30_MIN_ID: &str = "snooze30min";
+pub const BUTTON_SKIP_ID: &str = "skipreminder";
+pub const BUTTON_DRANK_ID: &str = "drankwater";
+pub const SELECT_DRANK_AMOUNT_ID: &str = "drankamount";
+
+/// Preset sizes, in ml, offered when acknowledging a reminder
+pub const DRINK_AMOUNTS_ML: [u32; 4] = [150, 250, 330, 500];
+
+pub fn get_drank_button() -> CreateButton {
+    CreateButton::new(BUTTON_DRANK_ID)
+        .label("C'est bu !")
+        .emoji('💧')
+        .style(ButtonStyle::Success)
+}
+
+pub fn get_drank_amount_select_menu() -> CreateSelectMenu {
+    let options = DRINK_AMOUNTS_ML
+        .iter()
+        .map(|amount| CreateSelectMenuOption::new(format!("{amount} ml"), amount.to_string()))
+        .collect();
+
+    CreateSelectMenu::new(
+        SELECT_DRANK_AMOUNT_ID,
+        CreateSelectMenuKind::String { options },
+    )
+    .placeholder("💧 C'est bu ! Quelle quantité ?")
+}
+
+pub fn get_snooze_10min_button() -> CreateButton {
+    CreateButton::new(BUTTON_SNOOZE_10_MIN_ID)
+        .label("Dans 10 min")
+        .emoji('⏰')
+        .style(ButtonStyle::Secondary)
+}
+
+pub fn get_snooze_30min_button() -> CreateButton {
+    CreateButton::new(BUTTON_SNOOZE_30_MIN_ID)
+        .label("Dans 30 min")
+        .emoji('⏰')
+        .style(ButtonStyle::Secondary)
+}
+
+pub fn get_skip_button() -> CreateButton {
+    CreateButton::new(BUTTON_SKIP_ID)
+        .label("Passer le prochain")
+        .emoji('⏭')
+        .style(ButtonStyle::Secondary)
+}
+
+pub fn is_reminder_component(custom_id: &str) -> bool {
+    [
+        BUTTON_SNOOZE_10_MIN_ID,
+        BUTTON_SNOOZE_30_MIN_ID,
+        BUTTON_SKIP_ID,
+        BUTTON_DRANK_ID,
+        SELECT_DRANK_AMOUNT_ID,
+    ]
+    .contains(&custom_id)
+}
+
+async fn snooze(user: &User, settings: &UserSettings, until: Option<NaiveDateTime>) -> String {
+    let Some(until) = until else {
+        return "⏭ Aucun rappel n'est prévu prochainement".to_string();
+    };
+    snooze_user_reminder(user, until).await;
+    info!("User {} snoozed its reminders until {}", user.name, until);
+
+    // A snooze ending in quiet hours waits for the window to open
+    match next_active_time(settings, until) {
+        Some(due) => format!(
+            "⏰ Prochain rappel vers {}",
+            settings.local_time(due).format("%H:%M")
+        ),
+        None => "⏭ Aucun rappel n'est prévu prochainement".to_string(),
+    }
+}
+
+async fn drank(
+    user: &User,
+    settings: &UserSettings,
+    now: NaiveDateTime,
+    amount_ml: Option<u32>,
+) -> String {
+    record_user_intake(user, now, amount_ml).await;
+    info!("User {} drank {:?} ml of water", user.name, amount_ml);
+
+    let time = settings.local_time(now).format("%H:%M");
+    match amount_ml {
+        Some(amount) => format!("💧 Bien joué ! {amount} ml bus à {time}"),
+        None => format!("💧 Bien joué ! Verre bu à {time}"),
+    }
+}
+
+/// Handles the components attached to reminders, they are removed once one is used
+pub async fn run(ctx: &Context, interaction: &ComponentInteraction) -> Result<(), serenity::Error> {
+    let user = &interaction.user;
+    let now = chrono::Utc::now().naive_utc();
+
+    // Reminders posted in the guild can be seen by others, only the mentioned user may answer
+    if interaction.guild_id.is_some()
+        && !interaction
+            .message
+            .mentions
+            .iter()
+            .any(|mentioned| mentioned.id == user.id)
+    {
+        interaction
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("Ce rappel ne vous est pas destiné.")
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let Some(settings) = lookup_user_settings(user).await else {
+        interaction
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("Vous n'êtes plus enregistré pour recevoir des rappels."),
+                ),
+            )
+            .await?;
+        return Ok(());
+    };
+
+    let has_drank =
+        [BUTTON_DRANK_ID, SELECT_DRANK_AMOUNT_ID].contains(&interaction.data.custom_id.as_str());
+    acknowledge_user_reminder(user, now, has_drank).await;
+
+    let status = match interaction.data.custom_id.as_str() {
+        BUTTON_SNOOZE_10_MIN_ID => {
+            snooze(user, &settings, Some(now + TimeDelta::minutes(10))).await
+        }
+        BUTTON_SNOOZE_30_MIN_ID => {
+            snooze(user, &settings, Some(now + TimeDelta::minutes(30))).await
+        }
+        BUTTON_SKIP_ID => {
+            // Skipping the upcoming reminder means waiting for the one after it
+            let last_reminded = lookup_last_reminded(user).await.unwrap_or(now);
+            let until = next_reminder_time(&settings, last_reminded)
+                .and_then(|next| next_reminder_time(&settings, next));
+            snooze(user, &settings, until).await
+        }
+        BUTTON_DRANK_ID => drank(user, &settings, now, None).await,
+        SELECT_DRANK_AMOUNT_ID => {
+            let amount_ml = match &interaction.data.kind {
+                ComponentInteractionDataKind::StringSelect { values } => {
+                    values.first().and_then(|amount| amount.parse().ok())
+                }
+                _ => None,
+            };
+            drank(user, &settings, now, amount_ml).await
+        }
+        _ => return Err(serenity::Error::Other("This value shouldn't exist >:(")),
+    };
+
+    interaction
+        .create_response(
+            &ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(format!("{}\n\n*{status}*", interaction.message.content))
+                    .components(vec![]),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use std::{collections::HashMap, env, sync::LazyLock};
+
+use chrono::{NaiveDateTime, TimeDelta};
+use serenity::all::User;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::{
+    registry::{
+        lookup_registered_users, lookup_user_settings, next_due_time, postpone_user_reminder,
+    },
+    schedule::{missed_reminders, next_active_time},
+    scheduler::SCHEDULER,
+};
+
+/// Missed reminders aren't counted any further, the summary would not be more useful
+const MAX_COUNTED_MISSED_REMINDERS: u32 = 100;
+
+/// Overdue reminders are spread over this duration with the spread policy
+fn catch_up_spread() -> TimeDelta {
+    TimeDelta::minutes(5)
+}
+
+/// Amount of reminders missed by users awaiting a summary, taken by their next reminder
+static MISSED_REMINDERS: LazyLock<RwLock<HashMap<User, u32>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// How reminders missed while the bot was offline are handled on startup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatchUpPolicy {
+    /// Drops the missed reminders, the next one comes one interval after the restart
+    Skip,
+    /// Sends a single reminder telling how many were missed
+    Summary,
+    /// Sends the overdue reminders gradually instead of all at once
+    Spread,
+}
+
+impl CatchUpPolicy {
+    /// `CATCH_UP_POLICY` (`skip`, `summary` or `spread`), skip when unset
+    pub fn from_env() -> Self {
+        match env::var("CATCH_UP_POLICY").as_deref() {
+            Ok("skip") | Err(_) => CatchUpPolicy::Skip,
+            Ok("summary") => CatchUpPolicy::Summary,
+            Ok("spread") => CatchUpPolicy::Spread,
+            Ok(policy) => {
+                warn!("Unknown catch-up policy '{}', defaulting to skip", policy);
+                CatchUpPolicy::Skip
+            }
+        }
+    }
+}
+
+pub async fn take_missed_reminders(user: &User) -> Option<u32> {
+    MISSED_REMINDERS.write().await.remove(user)
+}
+
+/// Handles the users whose next reminder went by while the bot was offline, must run once the
+/// users have been loaded and before the reminder loop starts
+pub async fn apply_catch_up_policy(policy: CatchUpPolicy, now: NaiveDateTime) {
+    let mut overdue = Vec::new();
+    for user in lookup_registered_users().await {
+        if let Some(due) = next_due_time(&user).await
+            && due < now
+        {
+            overdue.push((user, due));
+        }
+    }
+    if overdue.is_empty() {
+        return;
+    }
+
+    info!(
+        "{} users missed reminders while offline, catching up with the {:?} policy",
+        overdue.len(),
+        policy
+    );
+    match policy {
+        CatchUpPolicy::Skip => {
+            for (user, _) in overdue {
+                postpone_user_reminder(&user, now).await;
+            }
+        }
+        CatchUpPolicy::Summary => {
+            // The users are reminded as soon as their window allows it, mentioning what they
+            // missed
+            for (user, due) in overdue {
+                let Some(settings) = lookup_user_settings(&user).await else {
+                    continue;
+                };
+                let missed = missed_reminders(&settings, due, now, MAX_COUNTED_MISSED_REMINDERS);
+                SCHEDULER.schedule(&user, next_active_time(&settings, now));
+                MISSED_REMINDERS.write().await.insert(user, missed);
+            }
+        }
+        CatchUpPolicy::Spread => {
+            overdue.sort_by_key(|(_, due)| *due);
+            let count = overdue.len() as i32;
+            for (index, (user, _)) in overdue.into_iter().enumerate() {
+                let Some(settings) = lookup_user_settings(&user).await else {
+                    continue;
+                };
+                let offset = catch_up_spread() * index as i32 / count;
+                let due = next_active_time(&settings, now).map(|due| due + offset);
+                SCHEDULER.schedule(&user, due);
+            }
+        }
+    }
+}
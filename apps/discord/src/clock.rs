@@ -0,0 +1,17 @@
+use chrono::NaiveDateTime;
+
+/// Source of the current time, so scheduling can run on virtual time in tests
+pub trait Clock: Send + Sync {
+    /// Current time, in UTC
+    fn now(&self) -> NaiveDateTime;
+}
+
+/// The actual time of the system
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        chrono::Utc::now().naive_utc()
+    }
+}
@@ -0,0 +1,203 @@
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage, ResolvedOption,
+    ResolvedValue,
+};
+use tracing::{error, info};
+
+use crate::{
+    adaptive::{ADAPTIVE_STEP_PERCENT, AdaptiveBounds, quick_acknowledgement_delay},
+    commands::{NOT_REGISTERED_MESSAGE, pause::pause_line},
+    database::load_adaptive_adjustments,
+    registry::{
+        MAX_CUSTOM_FREQUENCY_MINUTES, MIN_CUSTOM_FREQUENCY_MINUTES, ReminderFrequency,
+        lookup_user_settings, update_user_adaptive,
+    },
+};
+
+/// Amount of adjustments listed by `/adaptive status`
+const SHOWN_ADJUSTMENTS: u32 = 10;
+
+async fn status(interaction: &CommandInteraction) -> CreateInteractionResponseMessage {
+    let user = &interaction.user;
+    let Some(settings) = lookup_user_settings(user).await else {
+        return CreateInteractionResponseMessage::new().content(NOT_REGISTERED_MESSAGE);
+    };
+
+    let pause = pause_line(user, &settings).await;
+    let Some(bounds) = settings.adaptive else {
+        return CreateInteractionResponseMessage::new().content(format!(
+            "Le mode adaptatif est désactivé, vous êtes rappelé(e) toutes les {}{pause}",
+            settings.frequency
+        ));
+    };
+
+    let adjustments = load_adaptive_adjustments(user, SHOWN_ADJUSTMENTS)
+        .await
+        .unwrap_or_else(|e| {
+            error!(
+                "Failed to load adaptive adjustments of user {} : {}",
+                user.name, e
+            );
+            Vec::new()
+        });
+
+    let history = if adjustments.is_empty() {
+        "Aucun ajustement pour le moment".to_string()
+    } else {
+        adjustments
+            .iter()
+            .map(|adjustment| {
+                format!(
+                    "`{}` {} → {} : {}",
+                    settings
+                        .local_time(adjustment.adjusted_at)
+                        .format("%d/%m %H:%M"),
+                    ReminderFrequency::Custom(adjustment.previous_minutes),
+                    ReminderFrequency::Custom(adjustment.new_minutes),
+                    adjustment.reason
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let embed = CreateEmbed::new()
+        .title("Mode adaptatif")
+        .description(format!(
+            "L'intervalle grandit de {ADAPTIVE_STEP_PERCENT}% quand vous buvez moins de {} min après un rappel, et diminue d'autant quand vous ignorez un rappel.{pause}",
+            quick_acknowledgement_delay().num_minutes()
+        ))
+        .field(
+            "Intervalle actuel",
+            format!("{}", settings.frequency),
+            true,
+        )
+        .field(
+            "Limites",
+            format!(
+                "{} - {}",
+                ReminderFrequency::Custom(bounds.min_minutes),
+                ReminderFrequency::Custom(bounds.max_minutes)
+            ),
+            true,
+        )
+        .field("Derniers ajustements", history, false);
+
+    CreateInteractionResponseMessage::new().embed(embed)
+}
+
+async fn enable(interaction: &CommandInteraction, options: &[ResolvedOption<'_>]) -> String {
+    let mut min = None;
+    let mut max = None;
+    for option in options {
+        match option {
+            ResolvedOption {
+                name: "min",
+                value: ResolvedValue::Integer(value),
+                ..
+            } => min = u32::try_from(*value).ok(),
+            ResolvedOption {
+                name: "max",
+                value: ResolvedValue::Integer(value),
+                ..
+            } => max = u32::try_from(*value).ok(),
+            _ => {}
+        }
+    }
+
+    let Some(bounds) = min
+        .zip(max)
+        .and_then(|(min, max)| AdaptiveBounds::new(min, max))
+    else {
+        return format!(
+            "Les limites doivent être comprises entre {MIN_CUSTOM_FREQUENCY_MINUTES} et {MAX_CUSTOM_FREQUENCY_MINUTES} minutes, le minimum avant le maximum"
+        );
+    };
+
+    if lookup_user_settings(&interaction.user)
+        .await
+        .is_some_and(|settings| settings.goal.is_some())
+    {
+        return "Votre intervalle suit votre objectif quotidien, désactivez-le d'abord avec `/goal disable`".to_string();
+    }
+
+    match update_user_adaptive(&interaction.user, Some(bounds)).await {
+        Some(frequency) => {
+            info!(
+                "User {} enabled the adaptive mode with {:?}",
+                interaction.user.name, bounds
+            );
+            format!(
+                "Mode adaptatif activé, vous êtes rappelé(e) toutes les {frequency} pour commencer"
+            )
+        }
+        None => NOT_REGISTERED_MESSAGE.to_string(),
+    }
+}
+
+async fn disable(interaction: &CommandInteraction) -> String {
+    match update_user_adaptive(&interaction.user, None).await {
+        Some(frequency) => {
+            info!("User {} disabled the adaptive mode", interaction.user.name);
+            format!("Mode adaptatif désactivé, vous resterez rappelé(e) toutes les {frequency}")
+        }
+        None => NOT_REGISTERED_MESSAGE.to_string(),
+    }
+}
+
+pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), serenity::Error> {
+    let options = interaction.data.options();
+
+    let message = match options.first() {
+        Some(ResolvedOption {
+            name: "enable",
+            value: ResolvedValue::SubCommand(options),
+            ..
+        }) => CreateInteractionResponseMessage::new().content(enable(interaction, options).await),
+        Some(ResolvedOption {
+            name: "disable", ..
+        }) => CreateInteractionResponseMessage::new().content(disable(interaction).await),
+        _ => status(interaction).await,
+    };
+
+    interaction
+        .create_response(
+            &ctx,
+            CreateInteractionResponse::Message(message.ephemeral(true)),
+        )
+        .await?;
+
+    Ok(())
+}
+
+fn bound_option(name: &str, description: &str) -> CreateCommandOption {
+    CreateCommandOption::new(CommandOptionType::Integer, name, description)
+        .min_int_value(MIN_CUSTOM_FREQUENCY_MINUTES as u64)
+        .max_int_value(MAX_CUSTOM_FREQUENCY_MINUTES as u64)
+        .required(true)
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("adaptive")
+        .description("Let your reminder interval adapt to how quickly you drink")
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "status",
+            "Show your current interval and its latest adjustments",
+        ))
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "enable",
+                "Enable the adaptive interval within the given bounds",
+            )
+            .add_sub_option(bound_option("min", "The shortest interval in minutes"))
+            .add_sub_option(bound_option("max", "The longest interval in minutes")),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "disable",
+            "Keep your current interval from now on",
+        ))
+}
@@ -0,0 +1,202 @@
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage, ResolvedOption,
+    ResolvedValue,
+};
+use tracing::info;
+
+use crate::{
+    commands::{NOT_REGISTERED_MESSAGE, pause::pause_line},
+    goal::{DailyGoal, MAX_DAILY_GOAL_ML, MIN_DAILY_GOAL_ML, goal_note},
+    registry::{lookup_user_settings, update_user_active_window, update_user_goal},
+    schedule::{ActiveWindow, parse_time_of_day},
+};
+
+async fn status(interaction: &CommandInteraction) -> CreateInteractionResponseMessage {
+    let user = &interaction.user;
+    let Some(settings) = lookup_user_settings(user).await else {
+        return CreateInteractionResponseMessage::new().content(NOT_REGISTERED_MESSAGE);
+    };
+
+    let (Some(goal), Some(window)) = (settings.goal, settings.active_window) else {
+        return CreateInteractionResponseMessage::new()
+            .content("Vous n'avez pas d'objectif quotidien, choisissez-en un avec `/goal set`");
+    };
+
+    let now = chrono::Utc::now().naive_utc();
+    let today = goal_note(user, &settings, now).await.unwrap_or_default();
+    let pause = pause_line(user, &settings).await;
+
+    let embed = CreateEmbed::new()
+        .title("Objectif quotidien")
+        .field("Objectif", format!("{} ml par jour", goal.target_ml), true)
+        .field(
+            "Plage horaire",
+            format!("{window} ({})", settings.timezone),
+            true,
+        )
+        .field("Rythme", goal.pace(window).to_string(), false)
+        .field("Aujourd'hui", format!("{today}{pause}"), false);
+
+    CreateInteractionResponseMessage::new().embed(embed)
+}
+
+async fn set(interaction: &CommandInteraction, options: &[ResolvedOption<'_>]) -> String {
+    let user = &interaction.user;
+
+    let mut target = None;
+    let mut start = None;
+    let mut end = None;
+    for option in options {
+        match option {
+            ResolvedOption {
+                name: "target",
+                value: ResolvedValue::Integer(value),
+                ..
+            } => target = u32::try_from(*value).ok(),
+            ResolvedOption {
+                name: "start",
+                value: ResolvedValue::String(value),
+                ..
+            } => start = Some(*value),
+            ResolvedOption {
+                name: "end",
+                value: ResolvedValue::String(value),
+                ..
+            } => end = Some(*value),
+            _ => {}
+        }
+    }
+
+    let Some(goal) = target.and_then(DailyGoal::new) else {
+        return format!(
+            "L'objectif doit être compris entre {MIN_DAILY_GOAL_ML} et {MAX_DAILY_GOAL_ML} ml"
+        );
+    };
+
+    let Some(settings) = lookup_user_settings(user).await else {
+        return NOT_REGISTERED_MESSAGE.to_string();
+    };
+    if settings.cron.is_some() {
+        return "Un objectif quotidien ne peut pas suivre une expression cron, réenregistrez-vous avec un intervalle d'abord".to_string();
+    }
+
+    let window = match (start, end) {
+        (Some(start), Some(end)) => {
+            let Some(window) = parse_time_of_day(start)
+                .zip(parse_time_of_day(end))
+                .and_then(|(start, end)| ActiveWindow::new(start, end))
+            else {
+                return "Les heures doivent être au format `HH:MM`, comme `09:00`, et être différentes".to_string();
+            };
+            update_user_active_window(user, Some(window)).await;
+            window
+        }
+        (None, None) => match settings.active_window {
+            Some(window) => window,
+            None => {
+                return "Indiquez la plage horaire sur laquelle répartir votre objectif, avec `start` et `end`".to_string();
+            }
+        },
+        _ => return "Indiquez à la fois le début et la fin de la plage horaire".to_string(),
+    };
+
+    if update_user_goal(user, Some(goal)).await.is_none() {
+        return NOT_REGISTERED_MESSAGE.to_string();
+    }
+    info!(
+        "User {} set a daily goal of {} ml between {}",
+        user.name, goal.target_ml, window
+    );
+    format!(
+        "Objectif de {} ml par jour entre **{window}** : {}. Les rappels vous diront si vous êtes en retard.",
+        goal.target_ml,
+        goal.pace(window)
+    )
+}
+
+async fn disable(interaction: &CommandInteraction) -> String {
+    match update_user_goal(&interaction.user, None).await {
+        Some(settings) => {
+            info!("User {} disabled its daily goal", interaction.user.name);
+            format!(
+                "Objectif quotidien désactivé, vous resterez rappelé(e) toutes les {}",
+                settings.frequency
+            )
+        }
+        None => NOT_REGISTERED_MESSAGE.to_string(),
+    }
+}
+
+pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), serenity::Error> {
+    let options = interaction.data.options();
+
+    let message = match options.first() {
+        Some(ResolvedOption {
+            name: "set",
+            value: ResolvedValue::SubCommand(options),
+            ..
+        }) => CreateInteractionResponseMessage::new().content(set(interaction, options).await),
+        Some(ResolvedOption {
+            name: "disable", ..
+        }) => CreateInteractionResponseMessage::new().content(disable(interaction).await),
+        _ => status(interaction).await,
+    };
+
+    interaction
+        .create_response(
+            &ctx,
+            CreateInteractionResponse::Message(message.ephemeral(true)),
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("goal")
+        .description("Spread a daily amount of water over your active hours")
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "status",
+            "Show your daily goal and how far along you are today",
+        ))
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "set",
+                "Set a daily goal, reminders are paced to reach it",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "target",
+                    "Amount to drink every day, in ml",
+                )
+                .min_int_value(MIN_DAILY_GOAL_ML as u64)
+                .max_int_value(MAX_DAILY_GOAL_ML as u64)
+                .required(true),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "start",
+                    "Local time at which your day starts, like 09:00, defaults to your window",
+                )
+                .required(false),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "end",
+                    "Local time at which your day ends, like 19:00, defaults to your window",
+                )
+                .required(false),
+            ),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "disable",
+            "Keep your current interval from now on",
+        ))
+}
@@ -1,2 +1,17 @@
+pub(crate) mod adaptive;
+pub(crate) mod goal;
+pub(crate) mod pause;
+pub(crate) mod presence;
 pub(crate) mod register;
+pub(crate) mod remindme;
+pub(crate) mod resume;
+pub(crate) mod schedule;
+pub(crate) mod timezone;
 pub(crate) mod unregister;
+pub(crate) mod vacation;
+pub(crate) mod voice;
+pub(crate) mod window;
+
+/// Answer to the commands used by someone who isn't registered
+pub(crate) const NOT_REGISTERED_MESSAGE: &str =
+    "Vous n'êtes pas enregistré pour recevoir des rappels.";
@@ -0,0 +1,101 @@
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    CreateInteractionResponse, CreateInteractionResponseMessage, ResolvedOption, ResolvedValue,
+    User,
+};
+use tracing::info;
+
+use crate::{
+    commands::NOT_REGISTERED_MESSAGE,
+    registry::{
+        PauseState, UserSettings, lookup_user_pause, lookup_user_settings, pause_user_reminders,
+    },
+    schedule::parse_duration,
+};
+
+/// Describes a pause in the user's time zone, for every status output
+pub fn pause_status(settings: &UserSettings, pause: PauseState) -> String {
+    match pause {
+        PauseState::Indefinite => {
+            "⏸ Vos rappels sont en pause jusqu'à ce que vous utilisiez `/resume`".to_string()
+        }
+        PauseState::Until(until) => format!(
+            "⏸ Vos rappels sont en pause jusqu'au {}",
+            settings.local_time(until).format("%d/%m à %H:%M")
+        ),
+    }
+}
+
+/// The ongoing pause of the user on a line of its own, to append to a status output, empty when
+/// the user isn't paused
+pub async fn pause_line(user: &User, settings: &UserSettings) -> String {
+    let now = chrono::Utc::now().naive_utc();
+    match lookup_user_pause(user).await {
+        Some(PauseState::Until(until)) if until <= now => String::new(),
+        Some(pause) => format!("\n{}", pause_status(settings, pause)),
+        None => String::new(),
+    }
+}
+
+pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), serenity::Error> {
+    let user = &interaction.user;
+    let now = chrono::Utc::now().naive_utc();
+
+    let mut duration = None;
+    for option in interaction.data.options() {
+        if let ResolvedOption {
+            name: "duration",
+            value: ResolvedValue::String(value),
+            ..
+        } = option
+        {
+            duration = Some(value);
+        }
+    }
+
+    let pause = match duration {
+        None => Some(PauseState::Indefinite),
+        Some(value) => parse_duration(value)
+            .and_then(|duration| now.checked_add_signed(duration))
+            .map(PauseState::Until),
+    };
+
+    let content = match (lookup_user_settings(user).await, pause) {
+        (None, _) => NOT_REGISTERED_MESSAGE.to_string(),
+        (Some(_), None) => "La durée doit être au format `30m`, `2h`, `1h30` ou `3j`".to_string(),
+        (Some(settings), Some(pause)) => {
+            if pause_user_reminders(user, pause).await {
+                info!("User {} paused its reminders", user.name);
+                pause_status(&settings, pause)
+            } else {
+                NOT_REGISTERED_MESSAGE.to_string()
+            }
+        }
+    };
+
+    interaction
+        .create_response(
+            &ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("pause")
+        .description("Pause your reminders without losing your settings")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "duration",
+                "How long to pause, like 30m, 2h, 1h30 or 3j, until /resume if not specified",
+            )
+            .required(false),
+        )
+}
@@ -0,0 +1,84 @@
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    CreateInteractionResponse, CreateInteractionResponseMessage, ResolvedOption, ResolvedValue,
+};
+use tracing::info;
+
+use crate::{
+    commands::{NOT_REGISTERED_MESSAGE, pause::pause_line},
+    registry::{lookup_user_settings, update_user_presence_aware},
+};
+
+pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), serenity::Error> {
+    let user = &interaction.user;
+
+    let mut enabled = None;
+    for option in interaction.data.options() {
+        if let ResolvedOption {
+            name: "enabled",
+            value: ResolvedValue::Boolean(value),
+            ..
+        } = option
+        {
+            enabled = Some(value);
+        }
+    }
+
+    let content = match enabled {
+        Some(enabled) => {
+            if update_user_presence_aware(user, enabled).await {
+                info!(
+                    "User {} turned presence awareness {}",
+                    user.name,
+                    if enabled { "on" } else { "off" }
+                );
+                if enabled {
+                    "Vos rappels attendront désormais que vous soyez en ligne, et pas en mode Ne pas déranger".to_string()
+                } else {
+                    "Vos rappels seront envoyés quel que soit votre statut".to_string()
+                }
+            } else {
+                NOT_REGISTERED_MESSAGE.to_string()
+            }
+        }
+        None => match lookup_user_settings(user).await {
+            Some(settings) => {
+                let pause = pause_line(user, &settings).await;
+                if settings.presence_aware {
+                    format!(
+                        "Vos rappels attendent que vous soyez en ligne, et pas en mode Ne pas déranger{pause}"
+                    )
+                } else {
+                    format!("Vos rappels sont envoyés quel que soit votre statut{pause}")
+                }
+            }
+            None => NOT_REGISTERED_MESSAGE.to_string(),
+        },
+    };
+
+    interaction
+        .create_response(
+            &ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("presence")
+        .description("Only be reminded while you are online and not in Do Not Disturb")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "enabled",
+                "Whether reminders wait for you to be online, shows the current choice if not specified",
+            )
+            .required(false),
+        )
+}
@@ -1,9 +1,10 @@
 use std::time::Duration;
 
+use chrono_tz::Tz;
 use serenity::all::{
     Colour, CommandInteraction, CommandOptionType, CreateCommand, CreateCommandOption, CreateEmbed,
     CreateInteractionResponse, CreateInteractionResponseFollowup, CreateInteractionResponseMessage,
-    ResolvedOption, ResolvedValue, prelude::*,
+    ResolvedOption, ResolvedValue, User, prelude::*,
 };
 use tracing::info;
 
@@ -11,20 +12,219 @@ use crate::{
     buttons::register_buttons::{
         get_1h_button, get_3h_button, get_30min_button, resolve_user_choice,
     },
-    registry::{insert_new_user_to_remind, lookup_active_reminders_count},
+    commands::{pause::pause_line, timezone::parse_timezone},
+    delivery::{DeliveryTarget, reminder_channel},
+    registry::{
+        MAX_CUSTOM_FREQUENCY_MINUTES, MIN_CUSTOM_FREQUENCY_MINUTES, ReminderFrequency,
+        UserSettings, insert_new_user_to_remind, lookup_active_reminders_count,
+        lookup_user_settings, next_due_time,
+    },
+    schedule::CronSchedule,
 };
 
+/// Fire times shown when registering with a cron expression
+const CRON_PREVIEW_LENGTH: usize = 5;
+
+const DELIVERY_CHOICES: [(&str, &str); 3] = [
+    ("Messages privés", "dm"),
+    ("Salon des rappels", "channel"),
+    ("Fil privé", "thread"),
+];
+
+const FALLBACK_CHOICES: [(&str, &str); 3] = [
+    ("Salon des rappels", "channel"),
+    ("Fil privé", "thread"),
+    ("Aucun", "none"),
+];
+
+async fn registered_message(user: &User, settings: &UserSettings) -> String {
+    // Absences are kept when registering again, so the next reminder may be days away
+    let next_reminder = match next_due_time(user).await {
+        Some(due) => format!(
+            "prochain rappel le {}",
+            settings.local_time(due).format("%d/%m à %H:%M")
+        ),
+        None => "aucun rappel n'est prévu".to_string(),
+    };
+    let pause = pause_line(user, settings).await;
+    let rhythm = match &settings.cron {
+        Some(cron) => format!("selon l'expression cron `{}`", cron.expression()),
+        None => format!("toutes les {}", settings.frequency),
+    };
+    let fallback = match settings.fallback {
+        Some(fallback) => format!(", ou {fallback} s'ils échouent"),
+        None => String::new(),
+    };
+    format!(
+        "Vous serez rappelé(e) {rhythm} (fuseau horaire {}), {next_reminder}\nLes rappels seront envoyés {}{fallback}{pause}",
+        settings.timezone, settings.delivery
+    )
+}
+
+/// Lists the next fire times of a cron schedule, in the user's time zone
+fn cron_preview(settings: &UserSettings, cron: &CronSchedule) -> String {
+    let now = chrono::Utc::now().naive_utc();
+    cron.upcoming(settings.timezone, now)
+        .take(CRON_PREVIEW_LENGTH)
+        .fold("Prochains rappels :".to_string(), |preview, time| {
+            format!(
+                "{preview}\n- {}",
+                settings.local_time(time).format("%d/%m/%Y %H:%M")
+            )
+        })
+}
+
 pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), serenity::Error> {
     let options = &interaction.data.options();
 
-    let target = if let Some(ResolvedOption {
-        value: ResolvedValue::User(target, _),
-        ..
-    }) = options.first()
-    {
-        target
-    } else {
-        &interaction.user
+    let mut target = &interaction.user;
+    let mut custom_minutes = None;
+    let mut zone = None;
+    let mut delivery = None;
+    let mut fallback = None;
+    let mut cron_expression = None;
+    for option in options {
+        match option {
+            ResolvedOption {
+                name: "target",
+                value: ResolvedValue::User(user, _),
+                ..
+            } => target = user,
+            ResolvedOption {
+                name: "interval",
+                value: ResolvedValue::Integer(minutes),
+                ..
+            } => custom_minutes = Some(*minutes),
+            ResolvedOption {
+                name: "timezone",
+                value: ResolvedValue::String(value),
+                ..
+            } => zone = Some(*value),
+            ResolvedOption {
+                name: "delivery",
+                value: ResolvedValue::String(value),
+                ..
+            } => delivery = DeliveryTarget::from_choice(value),
+            ResolvedOption {
+                name: "fallback",
+                value: ResolvedValue::String(value),
+                ..
+            } => fallback = Some(DeliveryTarget::from_choice(value)),
+            ResolvedOption {
+                name: "cron",
+                value: ResolvedValue::String(value),
+                ..
+            } => cron_expression = Some(*value),
+            _ => {}
+        }
+    }
+
+    let previous_settings = lookup_user_settings(target).await;
+
+    let timezone = match zone {
+        Some(zone) => match parse_timezone(zone) {
+            Some(timezone) => timezone,
+            None => {
+                interaction
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content(format!("Le fuseau horaire `{zone}` est inconnu, utilisez un nom IANA comme `Europe/Paris`"))
+                                .ephemeral(true),
+                        ),
+                    )
+                    .await?;
+                return Ok(());
+            }
+        },
+        None => previous_settings
+            .as_ref()
+            .map(|settings| settings.timezone)
+            .unwrap_or(Tz::UTC),
+    };
+
+    let delivery = delivery
+        .or(previous_settings.as_ref().map(|settings| settings.delivery))
+        .unwrap_or(DeliveryTarget::DirectMessage);
+    let fallback = fallback.unwrap_or(
+        previous_settings
+            .as_ref()
+            .and_then(|settings| settings.fallback),
+    );
+
+    let needs_reminder_channel = delivery.needs_reminder_channel()
+        || fallback.is_some_and(DeliveryTarget::needs_reminder_channel);
+    if needs_reminder_channel && reminder_channel().is_none() {
+        interaction
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("Aucun salon des rappels n'est configuré sur ce serveur, seuls les messages privés sont disponibles")
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let cron = match cron_expression {
+        Some(_) if custom_minutes.is_some() => {
+            interaction
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("Choisissez soit un intervalle, soit une expression cron, mais pas les deux")
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+        Some(expression) => {
+            match CronSchedule::parse(expression, timezone, chrono::Utc::now().naive_utc()) {
+                Ok(cron) => Some(cron),
+                Err(why) => {
+                    interaction
+                        .create_response(
+                            &ctx,
+                            CreateInteractionResponse::Message(
+                                CreateInteractionResponseMessage::new()
+                                    .content(why)
+                                    .ephemeral(true),
+                            ),
+                        )
+                        .await?;
+                    return Ok(());
+                }
+            }
+        }
+        None => None,
+    };
+
+    // Re-registering keeps the previously chosen preferences, but a new frequency replaces the
+    // cron schedule and the daily goal. An adaptive interval stays within its bounds
+    let build_settings = |frequency, cron| match &previous_settings {
+        Some(previous) => UserSettings {
+            frequency: match previous.adaptive {
+                Some(bounds) => bounds.clamp_frequency(frequency),
+                None => frequency,
+            },
+            timezone,
+            delivery,
+            fallback,
+            cron,
+            goal: None,
+            ..previous.clone()
+        },
+        None => UserSettings {
+            delivery,
+            fallback,
+            cron,
+            ..UserSettings::new(frequency, timezone)
+        },
     };
 
     let info_embed = CreateEmbed::new()
@@ -48,6 +248,14 @@ pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(),
             "A votre rythme",
             "Adaptez votre alimentation à vos envies et votre rythme",
             false,
+        )
+        .field(
+            "Un autre rythme ?",
+            format!(
+                "Utilisez l'option `interval` de `/register` pour choisir un intervalle entre {MIN_CUSTOM_FREQUENCY_MINUTES} minutes et {} heures, ou l'option `cron` pour un horaire sur mesure",
+                MAX_CUSTOM_FREQUENCY_MINUTES / 60
+            ),
+            false,
         );
 
     let data = CreateInteractionResponseMessage::new()
@@ -59,6 +267,74 @@ pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(),
         info!("Cannot respond to slash command: {why}");
     }
 
+    if let Some(cron) = cron {
+        // The frequency is kept for the wording of the reminders
+        let frequency = previous_settings
+            .as_ref()
+            .map(|settings| settings.frequency)
+            .unwrap_or(ReminderFrequency::OneHour);
+        let settings = build_settings(frequency, Some(cron.clone()));
+        insert_new_user_to_remind(target, settings.clone(), chrono::Utc::now().naive_utc()).await;
+        info!(
+            "User {} with id {} has been registered for reminders following '{}'",
+            target.name,
+            target.id,
+            cron.expression()
+        );
+
+        interaction
+            .create_followup(
+                &ctx,
+                CreateInteractionResponseFollowup::new()
+                    .content(format!(
+                        "{}\n{}",
+                        registered_message(target, &settings).await,
+                        cron_preview(&settings, &cron)
+                    ))
+                    .ephemeral(true),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    if let Some(minutes) = custom_minutes {
+        let Some(frequency) = u32::try_from(minutes)
+            .ok()
+            .and_then(ReminderFrequency::custom)
+        else {
+            interaction
+                .create_followup(
+                    &ctx,
+                    CreateInteractionResponseFollowup::new()
+                        .content(format!(
+                            "L'intervalle doit être compris entre {MIN_CUSTOM_FREQUENCY_MINUTES} et {MAX_CUSTOM_FREQUENCY_MINUTES} minutes"
+                        ))
+                        .ephemeral(true),
+                )
+                .await?;
+            return Ok(());
+        };
+
+        let settings = build_settings(frequency, None);
+        insert_new_user_to_remind(target, settings.clone(), chrono::Utc::now().naive_utc()).await;
+        info!(
+            "User {} with id {} has been registered for reminders every {} minutes",
+            target.name,
+            target.id,
+            frequency.minutes()
+        );
+
+        interaction
+            .create_followup(
+                &ctx,
+                CreateInteractionResponseFollowup::new()
+                    .content(registered_message(target, &settings).await)
+                    .ephemeral(true),
+            )
+            .await?;
+        return Ok(());
+    }
+
     let msg = interaction
         .create_followup(
             &ctx,
@@ -93,7 +369,8 @@ pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(),
     };
 
     let frequency = resolve_user_choice(choice)?;
-    insert_new_user_to_remind(target, frequency).await;
+    let settings = build_settings(frequency, None);
+    insert_new_user_to_remind(target, settings.clone(), chrono::Utc::now().naive_utc()).await;
 
     let msg = format!(
         "User {} with id {} has been registered for reminders",
@@ -110,7 +387,7 @@ pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(),
             &ctx,
             CreateInteractionResponse::Message(
                 CreateInteractionResponseMessage::new()
-                    .content(format!("Vous serez rappelé(e) toutes les {frequency}"))
+                    .content(registered_message(target, &settings).await)
                     .ephemeral(true),
             ),
         )
@@ -123,4 +400,54 @@ pub fn register() -> CreateCommand {
     CreateCommand::new("register")
         .description("Register yourself to be reminded to drink enough water !")
         .add_option(CreateCommandOption::new(CommandOptionType::User, "target", "A user that needs to be reminded, if not specified, this will consider you as the user to remind").required(false))
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "interval",
+                "A custom reminder interval in minutes, between 10 minutes and 8 hours",
+            )
+            .min_int_value(MIN_CUSTOM_FREQUENCY_MINUTES as u64)
+            .max_int_value(MAX_CUSTOM_FREQUENCY_MINUTES as u64)
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "timezone",
+                "Your IANA timezone, like Europe/Paris, defaults to UTC or your previous choice",
+            )
+            .set_autocomplete(true)
+            .required(false),
+        )
+        .add_option(
+            DELIVERY_CHOICES.iter().fold(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "delivery",
+                    "Where reminders are sent, defaults to DMs or your previous choice",
+                )
+                .required(false),
+                |option, (name, value)| option.add_string_choice(*name, *value),
+            ),
+        )
+        .add_option(
+            FALLBACK_CHOICES.iter().fold(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "fallback",
+                    "Where reminders are sent when the first choice fails, like closed DMs",
+                )
+                .required(false),
+                |option, (name, value)| option.add_string_choice(*name, *value),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "cron",
+                "A cron expression with seconds, like 0 */45 9-18 * * MON-FRI, replacing the interval",
+            )
+            .max_length(100)
+            .required(false),
+        )
 }
@@ -0,0 +1,117 @@
+use chrono::TimeDelta;
+use chrono_tz::Tz;
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    CreateInteractionResponse, CreateInteractionResponseMessage, ResolvedOption, ResolvedValue,
+};
+use tracing::{error, info};
+
+use crate::{
+    one_off::{MAX_PENDING_ONE_OFF_REMINDERS, add_one_off_reminder, lookup_pending_count},
+    registry::lookup_user_settings,
+    schedule::parse_duration,
+};
+
+/// Longest delay accepted for a one-off reminder
+fn max_one_off_delay() -> TimeDelta {
+    TimeDelta::days(7)
+}
+
+const MAX_NOTE_LENGTH: u16 = 200;
+
+pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), serenity::Error> {
+    let user = &interaction.user;
+    let now = chrono::Utc::now().naive_utc();
+
+    let mut delay = None;
+    let mut note = None;
+    for option in interaction.data.options() {
+        match option {
+            ResolvedOption {
+                name: "in",
+                value: ResolvedValue::String(value),
+                ..
+            } => delay = Some(value),
+            ResolvedOption {
+                name: "note",
+                value: ResolvedValue::String(value),
+                ..
+            } => note = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    let delay = delay.and_then(parse_duration);
+    let content = match delay {
+        None => "La durée doit être au format `30m`, `2h`, `1h30` ou `3j`".to_string(),
+        Some(delay) if delay > max_one_off_delay() => format!(
+            "Un rappel ponctuel ne peut pas être prévu plus de {} jours à l'avance",
+            max_one_off_delay().num_days()
+        ),
+        Some(_) if lookup_pending_count(user).await >= MAX_PENDING_ONE_OFF_REMINDERS => {
+            format!("Vous avez déjà {MAX_PENDING_ONE_OFF_REMINDERS} rappels ponctuels en attente")
+        }
+        Some(delay) => {
+            let due_at = now + delay;
+            match add_one_off_reminder(user, due_at, note).await {
+                Ok(()) => {
+                    info!("User {} asked for a reminder at {}", user.name, due_at);
+                    // Registered users see the time in their own zone
+                    let timezone = lookup_user_settings(user)
+                        .await
+                        .map(|settings| settings.timezone)
+                        .unwrap_or(Tz::UTC);
+                    format!(
+                        "⏰ C'est noté, je vous rappellerai de boire le {} ({timezone})",
+                        due_at
+                            .and_utc()
+                            .with_timezone(&timezone)
+                            .format("%d/%m à %H:%M")
+                    )
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to save one-off reminder of user {}: {}",
+                        user.name, e
+                    );
+                    "Le rappel n'a pas pu être enregistré, réessayez plus tard".to_string()
+                }
+            }
+        }
+    };
+
+    interaction
+        .create_response(
+            &ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("remindme")
+        .description("Get a single reminder to drink water, registered or not")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "in",
+                "When to be reminded, like 30m, 2h or 1h30",
+            )
+            .required(true),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "note",
+                "A note added to the reminder, like \"after the meeting\"",
+            )
+            .max_length(MAX_NOTE_LENGTH)
+            .required(false),
+        )
+}
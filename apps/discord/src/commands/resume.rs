@@ -0,0 +1,47 @@
+use serenity::all::{
+    CommandInteraction, Context, CreateCommand, CreateInteractionResponse,
+    CreateInteractionResponseMessage,
+};
+use tracing::info;
+
+use crate::commands::NOT_REGISTERED_MESSAGE;
+use crate::registry::{lookup_user_settings, next_due_time, resume_user_reminders};
+
+pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), serenity::Error> {
+    let user = &interaction.user;
+
+    let content = match lookup_user_settings(user).await {
+        None => NOT_REGISTERED_MESSAGE.to_string(),
+        Some(settings) => {
+            if resume_user_reminders(user).await {
+                info!("User {} resumed its reminders", user.name);
+                match next_due_time(user).await {
+                    Some(due) => format!(
+                        "▶ Vos rappels reprennent, prochain rappel vers {}",
+                        settings.local_time(due).format("%H:%M")
+                    ),
+                    None => "▶ Vos rappels reprennent".to_string(),
+                }
+            } else {
+                "Vos rappels ne sont pas en pause".to_string()
+            }
+        }
+    };
+
+    interaction
+        .create_response(
+            &ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("resume").description("Resume your paused reminders")
+}
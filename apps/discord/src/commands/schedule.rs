@@ -0,0 +1,307 @@
+use chrono::Weekday;
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage, ResolvedOption,
+    ResolvedValue,
+};
+use tracing::info;
+
+use crate::commands::{NOT_REGISTERED_MESSAGE, pause::pause_line};
+use crate::registry::{
+    MAX_CUSTOM_FREQUENCY_MINUTES, MIN_CUSTOM_FREQUENCY_MINUTES, ReminderFrequency,
+    lookup_user_settings, update_user_day_profiles,
+};
+use crate::schedule::{ActiveWindow, DayProfile, WEEKDAYS, parse_time_of_day, weekday_name};
+
+const DAYS_CHOICES: [(&str, &str); 10] = [
+    ("Jours de semaine", "weekdays"),
+    ("Week-end", "weekend"),
+    ("Lundi", "mon"),
+    ("Mardi", "tue"),
+    ("Mercredi", "wed"),
+    ("Jeudi", "thu"),
+    ("Vendredi", "fri"),
+    ("Samedi", "sat"),
+    ("Dimanche", "sun"),
+    ("Tous les jours", "all"),
+];
+
+fn resolve_days(days: &str) -> Option<&'static [Weekday]> {
+    match days {
+        "weekdays" => Some(&WEEKDAYS[..5]),
+        "weekend" => Some(&WEEKDAYS[5..]),
+        "all" => Some(&WEEKDAYS),
+        "mon" => Some(&WEEKDAYS[0..1]),
+        "tue" => Some(&WEEKDAYS[1..2]),
+        "wed" => Some(&WEEKDAYS[2..3]),
+        "thu" => Some(&WEEKDAYS[3..4]),
+        "fri" => Some(&WEEKDAYS[4..5]),
+        "sat" => Some(&WEEKDAYS[5..6]),
+        "sun" => Some(&WEEKDAYS[6..7]),
+        _ => None,
+    }
+}
+
+async fn view(interaction: &CommandInteraction) -> CreateInteractionResponseMessage {
+    let Some(settings) = lookup_user_settings(&interaction.user).await else {
+        return CreateInteractionResponseMessage::new().content(NOT_REGISTERED_MESSAGE);
+    };
+
+    let default_profile = DayProfile::On {
+        frequency: settings.frequency,
+        active_window: settings.active_window,
+    };
+
+    let pause = pause_line(&interaction.user, &settings).await;
+
+    // A cron schedule replaces the whole week
+    if let Some(cron) = &settings.cron {
+        let embed = CreateEmbed::new()
+            .title("Votre semaine")
+            .description(format!(
+                "Selon l'expression cron `{}` ({}){pause}",
+                cron.expression(),
+                settings.timezone
+            ));
+        return CreateInteractionResponseMessage::new().embed(embed);
+    }
+
+    let embed = WEEKDAYS.iter().fold(
+        CreateEmbed::new()
+            .title("Votre semaine")
+            .description(format!(
+                "Par défaut : {default_profile} ({}){pause}",
+                settings.timezone
+            )),
+        |embed, weekday| {
+            let day = match settings.profiles.get(*weekday) {
+                Some(profile) => profile.to_string(),
+                None => "par défaut".to_string(),
+            };
+            embed.field(weekday_name(*weekday), day, false)
+        },
+    );
+
+    CreateInteractionResponseMessage::new().embed(embed)
+}
+
+async fn set(interaction: &CommandInteraction, options: &[ResolvedOption<'_>]) -> String {
+    let mut days = None;
+    let mut interval = None;
+    let mut start = None;
+    let mut end = None;
+    let mut off = false;
+    for option in options {
+        match option {
+            ResolvedOption {
+                name: "days",
+                value: ResolvedValue::String(value),
+                ..
+            } => days = resolve_days(value),
+            ResolvedOption {
+                name: "interval",
+                value: ResolvedValue::Integer(value),
+                ..
+            } => interval = Some(*value),
+            ResolvedOption {
+                name: "start",
+                value: ResolvedValue::String(value),
+                ..
+            } => start = Some(*value),
+            ResolvedOption {
+                name: "end",
+                value: ResolvedValue::String(value),
+                ..
+            } => end = Some(*value),
+            ResolvedOption {
+                name: "off",
+                value: ResolvedValue::Boolean(value),
+                ..
+            } => off = *value,
+            _ => {}
+        }
+    }
+
+    let Some(days) = days else {
+        return "Ces jours sont inconnus".to_string();
+    };
+    let Some(settings) = lookup_user_settings(&interaction.user).await else {
+        return NOT_REGISTERED_MESSAGE.to_string();
+    };
+
+    let profile = if off {
+        DayProfile::Off
+    } else {
+        let frequency = match interval {
+            Some(minutes) => match u32::try_from(minutes)
+                .ok()
+                .and_then(ReminderFrequency::custom)
+            {
+                Some(frequency) => frequency,
+                None => {
+                    return format!(
+                        "L'intervalle doit être compris entre {MIN_CUSTOM_FREQUENCY_MINUTES} et {MAX_CUSTOM_FREQUENCY_MINUTES} minutes"
+                    );
+                }
+            },
+            None => settings.frequency,
+        };
+
+        let active_window = match (start, end) {
+            (None, None) => settings.active_window,
+            (Some(start), Some(end)) => match (parse_time_of_day(start), parse_time_of_day(end)) {
+                (Some(start), Some(end)) => match ActiveWindow::new(start, end) {
+                    Some(window) => Some(window),
+                    None => {
+                        return "Le début et la fin de la plage horaire doivent être différents"
+                            .to_string();
+                    }
+                },
+                _ => {
+                    return "Les heures doivent être au format `HH:MM`, comme `09:00`".to_string();
+                }
+            },
+            _ => return "Indiquez à la fois le début et la fin de la plage horaire".to_string(),
+        };
+
+        DayProfile::On {
+            frequency,
+            active_window,
+        }
+    };
+
+    if !update_user_day_profiles(&interaction.user, days, Some(profile)).await {
+        return NOT_REGISTERED_MESSAGE.to_string();
+    }
+    info!(
+        "User {} set the profile of {:?} to {:?}",
+        interaction.user.name, days, profile
+    );
+
+    let day_names = days
+        .iter()
+        .map(|weekday| weekday_name(*weekday))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("{day_names} : {profile}")
+}
+
+async fn reset(interaction: &CommandInteraction, options: &[ResolvedOption<'_>]) -> String {
+    let days = options.iter().find_map(|option| match option {
+        ResolvedOption {
+            name: "days",
+            value: ResolvedValue::String(value),
+            ..
+        } => resolve_days(value),
+        _ => None,
+    });
+
+    let Some(days) = days else {
+        return "Ces jours sont inconnus".to_string();
+    };
+
+    if !update_user_day_profiles(&interaction.user, days, None).await {
+        return NOT_REGISTERED_MESSAGE.to_string();
+    }
+    info!(
+        "User {} reset the profile of {:?}",
+        interaction.user.name, days
+    );
+
+    "Ces jours suivent désormais votre rythme par défaut".to_string()
+}
+
+pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), serenity::Error> {
+    let options = interaction.data.options();
+
+    let message = match options.first() {
+        Some(ResolvedOption {
+            name: "set",
+            value: ResolvedValue::SubCommand(options),
+            ..
+        }) => CreateInteractionResponseMessage::new().content(set(interaction, options).await),
+        Some(ResolvedOption {
+            name: "reset",
+            value: ResolvedValue::SubCommand(options),
+            ..
+        }) => CreateInteractionResponseMessage::new().content(reset(interaction, options).await),
+        _ => view(interaction).await,
+    };
+
+    interaction
+        .create_response(
+            &ctx,
+            CreateInteractionResponse::Message(message.ephemeral(true)),
+        )
+        .await?;
+
+    Ok(())
+}
+
+fn days_option() -> CreateCommandOption {
+    DAYS_CHOICES.iter().fold(
+        CreateCommandOption::new(CommandOptionType::String, "days", "The days to change")
+            .required(true),
+        |option, (name, value)| option.add_string_choice(*name, *value),
+    )
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("schedule")
+        .description("View or edit your reminders schedule for each day of the week")
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "view",
+            "Show the schedule of each day of the week",
+        ))
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "set",
+                "Change the schedule of some days, unspecified values use your default schedule",
+            )
+            .add_sub_option(days_option())
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "interval",
+                    "The reminder interval in minutes, between 10 minutes and 8 hours",
+                )
+                .min_int_value(MIN_CUSTOM_FREQUENCY_MINUTES as u64)
+                .max_int_value(MAX_CUSTOM_FREQUENCY_MINUTES as u64)
+                .required(false),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "start",
+                    "Local time at which reminders start, like 09:00",
+                )
+                .required(false),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "end",
+                    "Local time at which reminders stop, like 19:00",
+                )
+                .required(false),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "off",
+                    "No reminders at all on these days",
+                )
+                .required(false),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "reset",
+                "Make some days follow your default schedule again",
+            )
+            .add_sub_option(days_option()),
+        )
+}
@@ -0,0 +1,116 @@
+use chrono_tz::{TZ_VARIANTS, Tz};
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateAutocompleteResponse, CreateCommand,
+    CreateCommandOption, CreateInteractionResponse, CreateInteractionResponseMessage,
+    ResolvedOption, ResolvedValue,
+};
+use tracing::info;
+
+use crate::commands::{NOT_REGISTERED_MESSAGE, pause::pause_line};
+use crate::registry::{lookup_user_settings, update_user_timezone};
+
+/// Discord refuses autocomplete responses with more than 25 choices
+const MAX_AUTOCOMPLETE_CHOICES: usize = 25;
+
+pub fn parse_timezone(zone: &str) -> Option<Tz> {
+    zone.trim().parse::<Tz>().ok()
+}
+
+pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), serenity::Error> {
+    let user = &interaction.user;
+
+    let zone = interaction
+        .data
+        .options()
+        .into_iter()
+        .find_map(|option| match option {
+            ResolvedOption {
+                name: "zone",
+                value: ResolvedValue::String(zone),
+                ..
+            } => Some(zone),
+            _ => None,
+        });
+
+    let content = match zone {
+        None => match lookup_user_settings(user).await {
+            Some(settings) => format!(
+                "Votre fuseau horaire est **{}**, il y est actuellement {}{}",
+                settings.timezone,
+                settings
+                    .local_time(chrono::Utc::now().naive_utc())
+                    .format("%H:%M"),
+                pause_line(user, &settings).await
+            ),
+            None => NOT_REGISTERED_MESSAGE.to_string(),
+        },
+        Some(zone) => match parse_timezone(zone) {
+            None => format!(
+                "Le fuseau horaire `{zone}` est inconnu, utilisez un nom IANA comme `Europe/Paris`"
+            ),
+            Some(timezone) => {
+                if update_user_timezone(user, timezone).await {
+                    info!(
+                        "User {} with id {} is now in timezone {}",
+                        user.name, user.id, timezone
+                    );
+                    format!("Votre fuseau horaire est désormais **{timezone}**")
+                } else {
+                    NOT_REGISTERED_MESSAGE.to_string()
+                }
+            }
+        },
+    };
+
+    interaction
+        .create_response(
+            &ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Suggests IANA timezones matching what the user typed so far, used by every `zone` / `timezone` option
+pub async fn autocomplete(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+) -> Result<(), serenity::Error> {
+    let typed = interaction
+        .data
+        .autocomplete()
+        .map(|option| option.value.to_lowercase())
+        .unwrap_or_default();
+
+    let choices = TZ_VARIANTS
+        .iter()
+        .map(|timezone| timezone.name())
+        .filter(|name| name.to_lowercase().contains(&typed))
+        .take(MAX_AUTOCOMPLETE_CHOICES)
+        .fold(CreateAutocompleteResponse::new(), |response, name| {
+            response.add_string_choice(name, name)
+        });
+
+    interaction
+        .create_response(&ctx, CreateInteractionResponse::Autocomplete(choices))
+        .await
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("timezone")
+        .description("Show or change the timezone used for your reminders")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "zone",
+                "An IANA timezone, like Europe/Paris, if not specified, shows your current one",
+            )
+            .set_autocomplete(true)
+            .required(false),
+        )
+}
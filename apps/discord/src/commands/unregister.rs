@@ -1,6 +1,7 @@
 use serenity::all::{CommandInteraction, Context, CreateCommand, CreateInteractionResponseMessage};
 use tracing::info;
 
+use crate::commands::NOT_REGISTERED_MESSAGE;
 use crate::registry::{lookup_active_reminders_count, remove_user_from_reminders};
 
 pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), serenity::Error> {
@@ -14,7 +15,7 @@ pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(),
                 &ctx,
                 serenity::all::CreateInteractionResponse::Message(
                     CreateInteractionResponseMessage::new()
-                        .content("Vous n'êtes pas enregistré pour recevoir des rappels.")
+                        .content(NOT_REGISTERED_MESSAGE)
                         .ephemeral(true),
                 ),
             )
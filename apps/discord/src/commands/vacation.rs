@@ -0,0 +1,136 @@
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    CreateInteractionResponse, CreateInteractionResponseMessage, ResolvedOption, ResolvedValue,
+};
+use tracing::info;
+
+use crate::{
+    commands::{NOT_REGISTERED_MESSAGE, pause::pause_line},
+    registry::{add_user_absence, clear_user_absences, lookup_user_absences, lookup_user_settings},
+    schedule::{Absence, parse_date},
+};
+
+pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), serenity::Error> {
+    let user = &interaction.user;
+    let now = chrono::Utc::now().naive_utc();
+
+    let mut start = None;
+    let mut end = None;
+    let mut cancel = false;
+    for option in interaction.data.options() {
+        match option {
+            ResolvedOption {
+                name: "start",
+                value: ResolvedValue::String(value),
+                ..
+            } => start = Some(value),
+            ResolvedOption {
+                name: "end",
+                value: ResolvedValue::String(value),
+                ..
+            } => end = Some(value),
+            ResolvedOption {
+                name: "cancel",
+                value: ResolvedValue::Boolean(value),
+                ..
+            } => cancel = value,
+            _ => {}
+        }
+    }
+
+    let content = match (lookup_user_settings(user).await, start, end, cancel) {
+        (None, ..) => NOT_REGISTERED_MESSAGE.to_string(),
+        (Some(_), _, _, true) => match clear_user_absences(user).await {
+            0 => "Vous n'avez aucune absence prévue".to_string(),
+            count => {
+                info!("User {} cancelled {} absences", user.name, count);
+                format!(
+                    "{count} absence(s) annulée(s), vos rappels suivent à nouveau votre planning"
+                )
+            }
+        },
+        (Some(settings), None, None, false) => {
+            let absences = lookup_user_absences(user).await;
+            let pause = pause_line(user, &settings).await;
+            if absences.is_empty() {
+                format!("Vous n'avez aucune absence prévue{pause}")
+            } else {
+                absences.iter().fold(
+                    "🌴 Vos absences prévues :".to_string(),
+                    |content, absence| format!("{content}\n- {absence}"),
+                ) + &format!("\n({}){pause}", settings.timezone)
+            }
+        }
+        (Some(settings), Some(start), Some(end), false) => {
+            // Dates are understood in the user's time zone
+            let today = settings.local_time(now).date_naive();
+            match (parse_date(start, today), parse_date(end, today)) {
+                (Some(_), Some(end)) if end < today => {
+                    format!(
+                        "La fin de l'absence ({}) est déjà passée",
+                        end.format("%d/%m/%Y")
+                    )
+                }
+                (Some(start), Some(end)) => match Absence::new(start, end) {
+                    Some(absence) => {
+                        if add_user_absence(user, absence).await {
+                            info!("User {} planned an absence {}", user.name, absence);
+                            format!(
+                                "🌴 Aucun rappel ne sera envoyé {absence} ({}), ils reprendront automatiquement ensuite",
+                                settings.timezone
+                            )
+                        } else {
+                            NOT_REGISTERED_MESSAGE.to_string()
+                        }
+                    }
+                    None => "La fin de l'absence doit être après son début".to_string(),
+                },
+                _ => "Les dates doivent être au format `JJ/MM/AAAA`, `AAAA-MM-JJ` ou `JJ/MM`"
+                    .to_string(),
+            }
+        }
+        _ => "Indiquez à la fois le début et la fin de l'absence".to_string(),
+    };
+
+    interaction
+        .create_response(
+            &ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("vacation")
+        .description("Plan days off without reminders, or show the planned ones")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "start",
+                "First day off, like 24/12/2025 or 24/12",
+            )
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "end",
+                "Last day off, like 02/01/2026 or 02/01",
+            )
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "cancel",
+                "Cancel every planned absence",
+            )
+            .required(false),
+        )
+}
@@ -0,0 +1,133 @@
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    CreateInteractionResponse, CreateInteractionResponseMessage, ResolvedOption, ResolvedValue,
+};
+use tracing::info;
+
+use crate::{
+    commands::{NOT_REGISTERED_MESSAGE, pause::pause_line},
+    registry::{lookup_user_settings, update_user_voice_nudge},
+    voice::{
+        MAX_VOICE_NUDGE_MINUTES, MIN_VOICE_NUDGE_MINUTES, format_voice_time, lookup_voice_session,
+        reconnect_grace_period, refresh_voice_session,
+    },
+};
+
+async fn status(interaction: &CommandInteraction) -> String {
+    let user = &interaction.user;
+    let Some(settings) = lookup_user_settings(user).await else {
+        return NOT_REGISTERED_MESSAGE.to_string();
+    };
+    let Some(minutes) = settings.voice_nudge_minutes else {
+        return "Les rappels en vocal sont désactivés, activez-les avec l'option `minutes`"
+            .to_string();
+    };
+
+    let session = match lookup_voice_session(user).await {
+        Some(session) if session.left_at.is_none() => {
+            let now = chrono::Utc::now().naive_utc();
+            format!(
+                "\nVous êtes en vocal depuis {}",
+                format_voice_time(now - session.started_at)
+            )
+        }
+        _ => String::new(),
+    };
+    let pause = pause_line(user, &settings).await;
+    format!(
+        "Vous êtes rappelé(e) de boire toutes les {} passées en vocal{session}{pause}",
+        format_voice_time(chrono::TimeDelta::minutes(minutes as i64))
+    )
+}
+
+pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), serenity::Error> {
+    let user = &interaction.user;
+
+    let mut minutes = None;
+    let mut disable = false;
+    for option in interaction.data.options() {
+        match option {
+            ResolvedOption {
+                name: "minutes",
+                value: ResolvedValue::Integer(value),
+                ..
+            } => minutes = u32::try_from(value).ok(),
+            ResolvedOption {
+                name: "disable",
+                value: ResolvedValue::Boolean(value),
+                ..
+            } => disable = value,
+            _ => {}
+        }
+    }
+
+    let content = match (minutes, disable) {
+        (_, true) => {
+            if update_user_voice_nudge(user, None).await {
+                refresh_voice_session(user, chrono::Utc::now().naive_utc()).await;
+                info!("User {} disabled its voice nudges", user.name);
+                "Vous ne serez plus rappelé(e) de boire pendant vos sessions vocales".to_string()
+            } else {
+                NOT_REGISTERED_MESSAGE.to_string()
+            }
+        }
+        (Some(minutes), false)
+            if (MIN_VOICE_NUDGE_MINUTES..=MAX_VOICE_NUDGE_MINUTES).contains(&minutes) =>
+        {
+            if update_user_voice_nudge(user, Some(minutes)).await {
+                refresh_voice_session(user, chrono::Utc::now().naive_utc()).await;
+                info!(
+                    "User {} will be nudged every {} minutes in voice",
+                    user.name, minutes
+                );
+                format!(
+                    "Vous serez rappelé(e) de boire toutes les {} passées en vocal, en plus de vos rappels habituels. Les salons AFK ne comptent pas, et une déconnexion de moins de {} ne coupe pas la session.",
+                    format_voice_time(chrono::TimeDelta::minutes(minutes as i64)),
+                    format_voice_time(reconnect_grace_period())
+                )
+            } else {
+                NOT_REGISTERED_MESSAGE.to_string()
+            }
+        }
+        (Some(_), false) => format!(
+            "La durée doit être comprise entre {MIN_VOICE_NUDGE_MINUTES} et {MAX_VOICE_NUDGE_MINUTES} minutes"
+        ),
+        (None, false) => status(interaction).await,
+    };
+
+    interaction
+        .create_response(
+            &ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("voice")
+        .description("Be reminded to drink during long voice sessions")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "minutes",
+                "Time spent in voice channels before each reminder",
+            )
+            .min_int_value(MIN_VOICE_NUDGE_MINUTES as u64)
+            .max_int_value(MAX_VOICE_NUDGE_MINUTES as u64)
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "disable",
+                "Stop the reminders during voice sessions",
+            )
+            .required(false),
+        )
+}
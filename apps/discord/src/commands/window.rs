@@ -0,0 +1,134 @@
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    CreateInteractionResponse, CreateInteractionResponseMessage, ResolvedOption, ResolvedValue,
+};
+use tracing::info;
+
+use crate::{
+    commands::{NOT_REGISTERED_MESSAGE, pause::pause_line},
+    registry::{lookup_user_settings, update_user_active_window},
+    schedule::{ActiveWindow, parse_time_of_day},
+};
+
+pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), serenity::Error> {
+    let user = &interaction.user;
+
+    let mut start = None;
+    let mut end = None;
+    let mut disable = false;
+    for option in interaction.data.options() {
+        match option {
+            ResolvedOption {
+                name: "start",
+                value: ResolvedValue::String(value),
+                ..
+            } => start = Some(value),
+            ResolvedOption {
+                name: "end",
+                value: ResolvedValue::String(value),
+                ..
+            } => end = Some(value),
+            ResolvedOption {
+                name: "disable",
+                value: ResolvedValue::Boolean(value),
+                ..
+            } => disable = value,
+            _ => {}
+        }
+    }
+
+    let content = match (start, end, disable) {
+        // A daily goal is spread over the window, it can't go without one
+        (_, _, true)
+            if lookup_user_settings(user)
+                .await
+                .is_some_and(|settings| settings.goal.is_some()) =>
+        {
+            "Votre objectif quotidien a besoin d'une plage horaire, désactivez-le d'abord avec `/goal disable`".to_string()
+        }
+        (_, _, true) => {
+            if update_user_active_window(user, None).await {
+                info!("User {} disabled its active window", user.name);
+                "Vous serez désormais rappelé(e) à toute heure".to_string()
+            } else {
+                NOT_REGISTERED_MESSAGE.to_string()
+            }
+        }
+        (None, None, false) => match lookup_user_settings(user).await {
+            Some(settings) => {
+                let pause = pause_line(user, &settings).await;
+                match settings.active_window {
+                    Some(window) => format!(
+                        "Vous êtes rappelé(e) entre **{window}** ({}){pause}",
+                        settings.timezone
+                    ),
+                    None => format!("Vous êtes rappelé(e) à toute heure{pause}"),
+                }
+            }
+            None => NOT_REGISTERED_MESSAGE.to_string(),
+        },
+        (Some(start), Some(end), false) => {
+            match (parse_time_of_day(start), parse_time_of_day(end)) {
+                (Some(start), Some(end)) => match ActiveWindow::new(start, end) {
+                    Some(window) => {
+                        if update_user_active_window(user, Some(window)).await {
+                            info!("User {} set its active window to {}", user.name, window);
+                            format!(
+                                "Vous ne serez plus rappelé(e) qu'entre **{window}**, aucun rappel manqué ne sera envoyé à l'ouverture"
+                            )
+                        } else {
+                            NOT_REGISTERED_MESSAGE.to_string()
+                        }
+                    }
+                    None => {
+                        "Le début et la fin de la plage horaire doivent être différents".to_string()
+                    }
+                },
+                _ => "Les heures doivent être au format `HH:MM`, comme `09:00`".to_string(),
+            }
+        }
+        _ => "Indiquez à la fois le début et la fin de la plage horaire".to_string(),
+    };
+
+    interaction
+        .create_response(
+            &ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("window")
+        .description("Show or change the hours of the day during which you are reminded")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "start",
+                "Local time at which reminders start, like 09:00",
+            )
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "end",
+                "Local time at which reminders stop, like 19:00",
+            )
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "disable",
+                "Remove the active window and be reminded at any hour",
+            )
+            .required(false),
+        )
+}
@@ -1,15 +1,29 @@
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, Weekday};
+use chrono_tz::Tz;
 use rusqlite::{Connection, Result as SqliteResult};
 use serenity::all::User;
 use std::collections::HashMap;
 use std::path::Path;
-use std::sync::LazyLock;
-use tokio::sync::Mutex;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
 use tracing::{error, info, warn};
 
-use crate::registry::ReminderFrequency;
+use crate::adaptive::{AdaptiveBounds, AdjustmentReason};
+use crate::delivery::DeliveryTarget;
+use crate::dispatch::DeliveryOutcome;
+use crate::goal::DailyGoal;
+use crate::migrations::{MigrationError, migrate, schema_version};
+use crate::registry::{PauseState, ReminderFrequency, UserSettings};
+use crate::schedule::{Absence, ActiveWindow, CronSchedule, DayProfile, WEEKDAYS, WeekProfiles};
+use crate::voice::VoiceSession;
 
-pub static DATABASE: LazyLock<Mutex<Option<Connection>>> = LazyLock::new(|| Mutex::new(None));
+/// Handle to the thread owning the connection, queries run there so that disk I/O never blocks
+/// the async runtime
+static DATABASE: LazyLock<Mutex<Option<tokio_rusqlite::Connection>>> =
+    LazyLock::new(|| Mutex::new(None));
+
+/// How long a query waits for a lock held by another connection, like a backup tool's
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct UserData {
@@ -32,126 +46,360 @@ impl From<&User> for UserData {
     }
 }
 
-pub async fn init_database() -> SqliteResult<()> {
+fn frequency_to_db(frequency: ReminderFrequency) -> String {
+    match frequency {
+        ReminderFrequency::ThirtyMin => "ThirtyMin".to_string(),
+        ReminderFrequency::OneHour => "OneHour".to_string(),
+        ReminderFrequency::ThreeHours => "ThreeHours".to_string(),
+        ReminderFrequency::Custom(minutes) => format!("Custom:{minutes}"),
+    }
+}
+
+fn frequency_from_db(frequency: &str) -> Option<ReminderFrequency> {
+    match frequency {
+        "ThirtyMin" => Some(ReminderFrequency::ThirtyMin),
+        "OneHour" => Some(ReminderFrequency::OneHour),
+        "ThreeHours" => Some(ReminderFrequency::ThreeHours),
+        custom => custom
+            .strip_prefix("Custom:")
+            .and_then(|minutes| minutes.parse().ok())
+            .and_then(ReminderFrequency::custom),
+    }
+}
+
+/// Runs `query` on the connection thread, queries being run one after the other in the order
+/// they were made
+async fn call<T: Send + 'static>(
+    query: impl FnOnce(&mut Connection) -> SqliteResult<T> + Send + 'static,
+) -> SqliteResult<T> {
+    let database = DATABASE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .ok_or_else(|| {
+            rusqlite::Error::InvalidPath("Database not initialized".to_string().into())
+        })?;
+
+    database
+        .call(move |conn| Ok(query(conn)))
+        .await
+        .map_err(|_| {
+            rusqlite::Error::InvalidPath("Database connection closed".to_string().into())
+        })?
+}
+
+/// WAL lets reads go on while writing, and a busy connection is waited for instead of failing
+fn configure_connection(conn: &Connection) -> SqliteResult<()> {
+    let journal_mode: String =
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    info!("Database journal mode is {}", journal_mode);
+    Ok(())
+}
+
+/// Returns the schema version the database was brought to
+fn prepare_connection(conn: &mut Connection) -> Result<u32, MigrationError> {
+    configure_connection(conn)?;
+    migrate(conn)?;
+    Ok(schema_version(conn)?)
+}
+
+/// Opens the database, creating it when missing, and brings its schema up to date
+pub async fn init_database() -> Result<(), MigrationError> {
     let db_path = "database.sqlite";
 
     if !Path::new(db_path).exists() {
         info!("Database file does not exist, creating new database");
-        create_database(db_path).await?;
     } else {
         info!("Database file exists, connecting to existing database");
-        let conn = Connection::open(db_path)?;
-        *DATABASE.lock().await = Some(conn);
     }
+    let database = open_database(tokio_rusqlite::Connection::open(db_path).await).await?;
 
+    *DATABASE.lock().unwrap_or_else(|e| e.into_inner()) = Some(database);
     Ok(())
 }
 
-async fn create_database(db_path: &str) -> SqliteResult<()> {
-    let conn = Connection::open(db_path)?;
+async fn open_database(
+    database: tokio_rusqlite::Result<tokio_rusqlite::Connection>,
+) -> Result<tokio_rusqlite::Connection, MigrationError> {
+    let closed = |_| {
+        MigrationError::Sqlite(rusqlite::Error::InvalidPath(
+            "Database connection closed".to_string().into(),
+        ))
+    };
+    let database = database.map_err(|e| match e {
+        tokio_rusqlite::Error::Rusqlite(e) => MigrationError::Sqlite(e),
+        e => closed(e),
+    })?;
 
-    // Create users table
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS users (
-            user_id INTEGER PRIMARY KEY,
-            username TEXT NOT NULL,
-            reminder_frequency TEXT NOT NULL,
-            last_reminded TEXT NOT NULL,
-            created_at TEXT NOT NULL
-        )",
-        [],
-    )?;
+    let version = database
+        .call(|conn| Ok(prepare_connection(conn)))
+        .await
+        .map_err(closed)??;
+    info!("Database schema is at version {}", version);
+
+    Ok(database)
+}
 
-    info!("Database created successfully");
-    *DATABASE.lock().await = Some(conn);
+/// Closes the connection once every pending write went through, the database can't be used
+/// afterwards
+pub async fn close_database() -> SqliteResult<()> {
+    let Some(database) = DATABASE.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+        return Ok(());
+    };
+    database.close().await.map_err(|e| match e {
+        tokio_rusqlite::Error::Close((_, e)) | tokio_rusqlite::Error::Rusqlite(e) => e,
+        e => rusqlite::Error::InvalidPath(e.to_string().into()),
+    })?;
+
+    info!("Database closed");
     Ok(())
 }
 
+/// The database, like the registry and the scheduler, is global, so tests using it run one at a
+/// time
+#[cfg(test)]
+static TEST_LOCK: LazyLock<tokio::sync::Mutex<()>> = LazyLock::new(|| tokio::sync::Mutex::new(()));
+
+/// Replaces the database with an empty one kept in memory, which is the test's own until the
+/// returned guard is dropped
+#[cfg(test)]
+pub async fn init_memory_database() -> tokio::sync::MutexGuard<'static, ()> {
+    let guard = TEST_LOCK.lock().await;
+    let database = open_database(tokio_rusqlite::Connection::open_in_memory().await)
+        .await
+        .unwrap();
+    *DATABASE.lock().unwrap_or_else(|e| e.into_inner()) = Some(database);
+    guard
+}
+
+const TIME_OF_DAY_FORMAT: &str = "%H:%M";
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Timestamps are stored as RFC 3339 in UTC, always with nanoseconds: they read back exactly, and
+/// compare as text in the same order as in time
+pub fn timestamp_to_db(time: NaiveDateTime) -> String {
+    time.and_utc().to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+pub fn timestamp_from_db(time: &str) -> Option<NaiveDateTime> {
+    DateTime::parse_from_rfc3339(time)
+        .ok()
+        .map(|time| time.naive_utc())
+}
+
+fn active_window_to_db(active_window: Option<ActiveWindow>) -> (Option<String>, Option<String>) {
+    match active_window {
+        Some(window) => (
+            Some(window.start.format(TIME_OF_DAY_FORMAT).to_string()),
+            Some(window.end.format(TIME_OF_DAY_FORMAT).to_string()),
+        ),
+        None => (None, None),
+    }
+}
+
+fn active_window_from_db(start: Option<String>, end: Option<String>) -> Option<ActiveWindow> {
+    let start = NaiveTime::parse_from_str(&start?, TIME_OF_DAY_FORMAT).ok()?;
+    let end = NaiveTime::parse_from_str(&end?, TIME_OF_DAY_FORMAT).ok()?;
+    ActiveWindow::new(start, end)
+}
+
 pub async fn save_user_reminder(
     user: &User,
-    frequency: ReminderFrequency,
+    settings: &UserSettings,
     last_reminded: NaiveDateTime,
+    created_at: NaiveDateTime,
 ) -> SqliteResult<()> {
-    let db_guard = DATABASE.lock().await;
-    let conn = db_guard
-        .as_ref()
-        .ok_or_else(|| rusqlite::Error::InvalidPath("Database not initialized".into()))?;
-
-    let frequency_str = match frequency {
-        ReminderFrequency::ThirtyMin => "ThirtyMin",
-        ReminderFrequency::OneHour => "OneHour",
-        ReminderFrequency::ThreeHours => "ThreeHours",
-    };
+    let (active_start, active_end) = active_window_to_db(settings.active_window);
+    let params = (
+        user.id.get() as i64,
+        user.name.clone(),
+        frequency_to_db(settings.frequency),
+        timestamp_to_db(last_reminded),
+        timestamp_to_db(created_at),
+        settings.timezone.name(),
+        active_start,
+        active_end,
+        settings.adaptive.map(|bounds| bounds.min_minutes),
+        settings.adaptive.map(|bounds| bounds.max_minutes),
+        settings.delivery.to_db(),
+        settings.fallback.map(DeliveryTarget::to_db),
+        settings
+            .cron
+            .as_ref()
+            .map(|cron| cron.expression().to_string()),
+        settings.goal.map(|goal| goal.target_ml),
+        settings.presence_aware,
+        settings.voice_nudge_minutes,
+    );
 
-    conn.execute(
-        "INSERT OR REPLACE INTO users (user_id, username, reminder_frequency, last_reminded, created_at) 
-         VALUES (?1, ?2, ?3, ?4, ?5)",
-        rusqlite::params![
-            user.id.get() as i64,
-            user.name,
-            frequency_str,
-            last_reminded.to_string(),
-            chrono::Utc::now().naive_utc().to_string()
-        ],
-    )?;
+    call(move |conn| {
+        conn.execute(
+            "INSERT OR REPLACE INTO users (user_id, username, reminder_frequency, last_reminded, created_at, timezone, active_start, active_end, adaptive_min, adaptive_max, delivery_target, fallback_target, cron_expression, goal_ml, presence_aware, voice_nudge_minutes)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+            params,
+        )?;
+        Ok(())
+    })
+    .await?;
 
     info!("Saved user {} reminder to database", user.name);
     Ok(())
 }
 
-pub async fn load_user_reminders() -> SqliteResult<(
-    HashMap<UserData, ReminderFrequency>,
-    HashMap<UserData, NaiveDateTime>,
-)> {
-    let db_guard = DATABASE.lock().await;
-    let conn = db_guard.as_ref().ok_or_else(|| {
-        rusqlite::Error::InvalidPath("Database not initialized".to_string().into())
-    })?;
+/// Everything stored about a registered user
+#[derive(Debug, Clone)]
+pub struct StoredUser {
+    pub settings: UserSettings,
+    pub last_reminded: NaiveDateTime,
+    pub snoozed_until: Option<NaiveDateTime>,
+    pub awaiting_acknowledgement: bool,
+    /// Consecutive permanent delivery failures
+    pub delivery_failures: u32,
+    pub suspended: bool,
+    /// Private thread hosting the reminders of the user, once created
+    pub reminder_thread_id: Option<u64>,
+    pub pause: Option<PauseState>,
+    /// Upcoming and ongoing absences, earliest first
+    pub absences: Vec<Absence>,
+}
 
-    let mut stmt =
-        conn.prepare("SELECT user_id, username, reminder_frequency, last_reminded FROM users")?;
+/// Users whose last reminder can't be read are considered reminded at `now`
+pub async fn load_user_reminders(
+    now: NaiveDateTime,
+) -> SqliteResult<HashMap<UserData, StoredUser>> {
+    call(move |conn| load_user_reminders_from(conn, now)).await
+}
+
+fn load_user_reminders_from(
+    conn: &Connection,
+    now: NaiveDateTime,
+) -> SqliteResult<HashMap<UserData, StoredUser>> {
+    let mut stmt = conn.prepare(
+        "SELECT user_id, username, reminder_frequency, last_reminded, timezone, active_start, active_end, snoozed_until, adaptive_min, adaptive_max, awaiting_acknowledgement, delivery_failures, suspended, delivery_target, fallback_target, reminder_thread_id, paused, paused_until, cron_expression, goal_ml, presence_aware, voice_nudge_minutes FROM users",
+    )?;
 
     let user_iter = stmt.query_map([], |row| {
         let user_id: i64 = row.get(0)?;
         let username: String = row.get(1)?;
         let frequency_str: String = row.get(2)?;
         let last_reminded_str: String = row.get(3)?;
-
-        let frequency = match frequency_str.as_str() {
-            "ThirtyMin" => ReminderFrequency::ThirtyMin,
-            "OneHour" => ReminderFrequency::OneHour,
-            "ThreeHours" => ReminderFrequency::ThreeHours,
-            _ => {
+        let timezone_str: String = row.get(4)?;
+        let active_window = active_window_from_db(row.get(5)?, row.get(6)?);
+        let snoozed_until = row
+            .get::<_, Option<String>>(7)?
+            .and_then(|until| timestamp_from_db(&until));
+        let adaptive = match (row.get::<_, Option<u32>>(8)?, row.get::<_, Option<u32>>(9)?) {
+            (Some(min), Some(max)) => AdaptiveBounds::new(min, max),
+            _ => None,
+        };
+        let awaiting_acknowledgement: bool = row.get(10)?;
+        let delivery_failures: u32 = row.get(11)?;
+        let suspended: bool = row.get(12)?;
+        let delivery_str: String = row.get(13)?;
+        let fallback = row
+            .get::<_, Option<String>>(14)?
+            .and_then(|target| DeliveryTarget::from_db(&target));
+        let reminder_thread_id = row.get::<_, Option<i64>>(15)?.map(|id| id as u64);
+        let paused: bool = row.get(16)?;
+        let paused_until = row
+            .get::<_, Option<String>>(17)?
+            .and_then(|until| timestamp_from_db(&until));
+        let cron = row.get::<_, Option<String>>(18)?.and_then(|expression| {
+            let cron = CronSchedule::from_db(&expression);
+            if cron.is_none() {
                 warn!(
-                    "Unknown frequency '{}' for user {}, defaulting to OneHour",
-                    frequency_str, username
+                    "Invalid cron expression '{}' for user {}, using its frequency",
+                    expression, username
                 );
-                ReminderFrequency::OneHour
             }
+            cron
+        });
+        let goal = row.get::<_, Option<u32>>(19)?.and_then(DailyGoal::new);
+        let presence_aware: bool = row.get(20)?;
+        let voice_nudge_minutes: Option<u32> = row.get(21)?;
+        let pause = match (paused, paused_until) {
+            (false, _) => None,
+            (true, Some(until)) => Some(PauseState::Until(until)),
+            (true, None) => Some(PauseState::Indefinite),
         };
 
-        let last_reminded = NaiveDateTime::parse_from_str(&last_reminded_str, "%Y-%m-%d %H:%M:%S")
-            .unwrap_or_else(|_| {
-                warn!(
-                    "Invalid date format for user {}, using current time",
-                    username
-                );
-                chrono::Utc::now().naive_utc()
-            });
+        let frequency = frequency_from_db(&frequency_str).unwrap_or_else(|| {
+            warn!(
+                "Unknown frequency '{}' for user {}, defaulting to OneHour",
+                frequency_str, username
+            );
+            ReminderFrequency::OneHour
+        });
+
+        let last_reminded = timestamp_from_db(&last_reminded_str).unwrap_or_else(|| {
+            warn!(
+                "Invalid date format for user {}, using current time",
+                username
+            );
+            now
+        });
+
+        let timezone = timezone_str.parse::<Tz>().unwrap_or_else(|_| {
+            warn!(
+                "Unknown timezone '{}' for user {}, defaulting to UTC",
+                timezone_str, username
+            );
+            Tz::UTC
+        });
+
+        let delivery = DeliveryTarget::from_db(&delivery_str).unwrap_or_else(|| {
+            warn!(
+                "Unknown delivery target '{}' for user {}, defaulting to DirectMessage",
+                delivery_str, username
+            );
+            DeliveryTarget::DirectMessage
+        });
+
+        let settings = UserSettings {
+            active_window,
+            adaptive,
+            delivery,
+            fallback,
+            cron,
+            goal,
+            presence_aware,
+            voice_nudge_minutes,
+            ..UserSettings::new(frequency, timezone)
+        };
+
+        let stored_user = StoredUser {
+            settings,
+            last_reminded,
+            snoozed_until,
+            awaiting_acknowledgement,
+            delivery_failures,
+            suspended,
+            reminder_thread_id,
+            pause,
+            absences: Vec::new(),
+        };
 
-        Ok((user_id, username, frequency, last_reminded))
+        Ok((user_id, username, stored_user))
     })?;
 
+    let mut profiles = load_day_profiles(conn)?;
+    let mut absences = load_absences(conn)?;
+
     let mut registered_users = HashMap::new();
-    let mut last_reminded_times = HashMap::new();
 
     for result in user_iter {
         match result {
-            Ok((user_id, username, frequency, last_reminded)) => {
-                let user_data = UserData::new(user_id as u64, username.clone());
-                registered_users.insert(user_data.clone(), frequency);
-                last_reminded_times.insert(user_data, last_reminded);
-                info!("Loaded user {} with frequency {:?}", username, frequency);
+            Ok((user_id, username, mut stored_user)) => {
+                stored_user.settings.profiles =
+                    profiles.remove(&(user_id as u64)).unwrap_or_default();
+                stored_user.absences = absences.remove(&(user_id as u64)).unwrap_or_default();
+                info!(
+                    "Loaded user {} with frequency {:?} in timezone {}",
+                    username, stored_user.settings.frequency, stored_user.settings.timezone
+                );
+                registered_users.insert(UserData::new(user_id as u64, username), stored_user);
             }
             Err(e) => {
                 error!("Error loading user from database: {}", e);
@@ -160,19 +408,31 @@ pub async fn load_user_reminders() -> SqliteResult<(
     }
 
     info!("Loaded {} users from database", registered_users.len());
-    Ok((registered_users, last_reminded_times))
+    Ok(registered_users)
 }
 
 pub async fn remove_user_reminder(user: &User) -> SqliteResult<()> {
-    let db_guard = DATABASE.lock().await;
-    let conn = db_guard.as_ref().ok_or_else(|| {
-        rusqlite::Error::InvalidPath("Database not initialized".to_string().into())
-    })?;
-
-    conn.execute(
-        "DELETE FROM users WHERE user_id = ?1",
-        rusqlite::params![user.id.get() as i64],
-    )?;
+    let user_id = user.id.get() as i64;
+    call(move |conn| {
+        // Everything about the user goes, or nothing does
+        let tx = conn.transaction()?;
+        for table in [
+            "users",
+            "schedule_profiles",
+            "intake_events",
+            "adaptive_adjustments",
+            "delivery_attempts",
+            "absences",
+            "voice_sessions",
+        ] {
+            tx.execute(
+                &format!("DELETE FROM {table} WHERE user_id = ?1"),
+                rusqlite::params![user_id],
+            )?;
+        }
+        tx.commit()
+    })
+    .await?;
 
     info!("Removed user {} reminder from database", user.name);
     Ok(())
@@ -182,19 +442,987 @@ pub async fn update_user_last_reminded(
     user: &User,
     last_reminded: NaiveDateTime,
 ) -> SqliteResult<()> {
-    let db_guard = DATABASE.lock().await;
-    let conn = db_guard.as_ref().ok_or_else(|| {
-        rusqlite::Error::InvalidPath("Database not initialized".to_string().into())
-    })?;
+    let params = (timestamp_to_db(last_reminded), user.id.get() as i64);
+    call(move |conn| {
+        conn.execute(
+            "UPDATE users SET last_reminded = ?1, snoozed_until = NULL, awaiting_acknowledgement = 1 WHERE user_id = ?2",
+            params,
+        )
+    })
+    .await?;
+
+    info!(
+        "Updated last reminded time for user {} in database",
+        user.name
+    );
+    Ok(())
+}
+
+pub async fn update_user_timezone(user: &User, timezone: Tz) -> SqliteResult<()> {
+    let params = (timezone.name(), user.id.get() as i64);
+    call(move |conn| conn.execute("UPDATE users SET timezone = ?1 WHERE user_id = ?2", params))
+        .await?;
+
+    info!("Updated timezone for user {} in database", user.name);
+    Ok(())
+}
+
+pub async fn update_user_active_window(
+    user: &User,
+    active_window: Option<ActiveWindow>,
+) -> SqliteResult<()> {
+    let (active_start, active_end) = active_window_to_db(active_window);
+    let params = (active_start, active_end, user.id.get() as i64);
+    call(move |conn| {
+        conn.execute(
+            "UPDATE users SET active_start = ?1, active_end = ?2 WHERE user_id = ?3",
+            params,
+        )
+    })
+    .await?;
 
-    conn.execute(
-        "UPDATE users SET last_reminded = ?1 WHERE user_id = ?2",
-        rusqlite::params![last_reminded.to_string(), user.id.get() as i64],
+    info!("Updated active window for user {} in database", user.name);
+    Ok(())
+}
+
+fn load_day_profiles(conn: &Connection) -> SqliteResult<HashMap<u64, WeekProfiles>> {
+    let mut stmt = conn.prepare(
+        "SELECT user_id, weekday, enabled, reminder_frequency, active_start, active_end FROM schedule_profiles",
     )?;
 
+    let profile_iter = stmt.query_map([], |row| {
+        let user_id: i64 = row.get(0)?;
+        let weekday: u8 = row.get(1)?;
+        let enabled: bool = row.get(2)?;
+        let frequency_str: Option<String> = row.get(3)?;
+        let active_window = active_window_from_db(row.get(4)?, row.get(5)?);
+
+        let profile = match frequency_str.as_deref().and_then(frequency_from_db) {
+            Some(frequency) if enabled => Some(DayProfile::On {
+                frequency,
+                active_window,
+            }),
+            _ if enabled => None,
+            _ => Some(DayProfile::Off),
+        };
+
+        Ok((
+            user_id as u64,
+            WEEKDAYS.get(weekday as usize).copied(),
+            profile,
+        ))
+    })?;
+
+    let mut profiles = HashMap::<u64, WeekProfiles>::new();
+    for result in profile_iter {
+        match result {
+            Ok((user_id, Some(weekday), Some(profile))) => {
+                profiles
+                    .entry(user_id)
+                    .or_default()
+                    .set(weekday, Some(profile));
+            }
+            Ok((user_id, _, _)) => {
+                warn!("Ignoring invalid schedule profile of user {}", user_id);
+            }
+            Err(e) => {
+                error!("Error loading schedule profile from database: {}", e);
+            }
+        }
+    }
+
+    Ok(profiles)
+}
+
+fn load_absences(conn: &Connection) -> SqliteResult<HashMap<u64, Vec<Absence>>> {
+    let mut stmt =
+        conn.prepare("SELECT user_id, start_date, end_date FROM absences ORDER BY start_date")?;
+
+    let absence_iter = stmt.query_map([], |row| {
+        let user_id: i64 = row.get(0)?;
+        let start: String = row.get(1)?;
+        let end: String = row.get(2)?;
+        let absence = match (
+            NaiveDate::parse_from_str(&start, DATE_FORMAT),
+            NaiveDate::parse_from_str(&end, DATE_FORMAT),
+        ) {
+            (Ok(start), Ok(end)) => Absence::new(start, end),
+            _ => None,
+        };
+        Ok((user_id as u64, absence))
+    })?;
+
+    let mut absences = HashMap::<u64, Vec<Absence>>::new();
+    for result in absence_iter {
+        match result {
+            Ok((user_id, Some(absence))) => absences.entry(user_id).or_default().push(absence),
+            Ok((user_id, None)) => {
+                warn!("Ignoring invalid absence of user {}", user_id);
+            }
+            Err(e) => {
+                error!("Error loading absence from database: {}", e);
+            }
+        }
+    }
+
+    Ok(absences)
+}
+
+fn absence_to_db(user: &User, absence: &Absence) -> (i64, String, String) {
+    (
+        user.id.get() as i64,
+        absence.start.format(DATE_FORMAT).to_string(),
+        absence.end.format(DATE_FORMAT).to_string(),
+    )
+}
+
+pub async fn insert_absence(user: &User, absence: Absence) -> SqliteResult<()> {
+    let params = absence_to_db(user, &absence);
+    call(move |conn| {
+        conn.execute(
+            "INSERT INTO absences (user_id, start_date, end_date) VALUES (?1, ?2, ?3)",
+            params,
+        )
+    })
+    .await?;
+
+    info!("Saved absence of user {} to database", user.name);
+    Ok(())
+}
+
+/// Removes the given absences of the user
+pub async fn remove_absences(user: &User, absences: &[Absence]) -> SqliteResult<()> {
+    let absences: Vec<_> = absences
+        .iter()
+        .map(|absence| absence_to_db(user, absence))
+        .collect();
+    call(move |conn| {
+        for params in absences {
+            conn.execute(
+                "DELETE FROM absences WHERE user_id = ?1 AND start_date = ?2 AND end_date = ?3",
+                params,
+            )?;
+        }
+        Ok(())
+    })
+    .await?;
+
+    info!("Removed absences of user {} from database", user.name);
+    Ok(())
+}
+
+/// Saves the profile of the given days, removing it when None
+pub async fn save_day_profiles(
+    user: &User,
+    weekdays: &[Weekday],
+    profile: Option<DayProfile>,
+) -> SqliteResult<()> {
+    let user_id = user.id.get() as i64;
+    let weekdays: Vec<u32> = weekdays
+        .iter()
+        .map(|weekday| weekday.num_days_from_monday())
+        .collect();
+    call(move |conn| {
+        for weekday_index in weekdays {
+            match profile {
+                None => {
+                    conn.execute(
+                        "DELETE FROM schedule_profiles WHERE user_id = ?1 AND weekday = ?2",
+                        rusqlite::params![user_id, weekday_index],
+                    )?;
+                }
+                Some(DayProfile::Off) => {
+                    conn.execute(
+                        "INSERT OR REPLACE INTO schedule_profiles (user_id, weekday, enabled, reminder_frequency, active_start, active_end)
+                         VALUES (?1, ?2, 0, NULL, NULL, NULL)",
+                        rusqlite::params![user_id, weekday_index],
+                    )?;
+                }
+                Some(DayProfile::On {
+                    frequency,
+                    active_window,
+                }) => {
+                    let (active_start, active_end) = active_window_to_db(active_window);
+                    conn.execute(
+                        "INSERT OR REPLACE INTO schedule_profiles (user_id, weekday, enabled, reminder_frequency, active_start, active_end)
+                         VALUES (?1, ?2, 1, ?3, ?4, ?5)",
+                        rusqlite::params![
+                            user_id,
+                            weekday_index,
+                            frequency_to_db(frequency),
+                            active_start,
+                            active_end
+                        ],
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    })
+    .await?;
+
+    info!("Saved schedule profiles of user {} to database", user.name);
+    Ok(())
+}
+
+pub async fn update_user_snoozed_until(
+    user: &User,
+    snoozed_until: Option<NaiveDateTime>,
+) -> SqliteResult<()> {
+    let params = (snoozed_until.map(timestamp_to_db), user.id.get() as i64);
+    call(move |conn| {
+        conn.execute(
+            "UPDATE users SET snoozed_until = ?1 WHERE user_id = ?2",
+            params,
+        )
+    })
+    .await?;
+
+    info!("Updated snoozed time for user {} in database", user.name);
+    Ok(())
+}
+
+pub async fn insert_intake_event(
+    user: &User,
+    drank_at: NaiveDateTime,
+    amount_ml: Option<u32>,
+) -> SqliteResult<()> {
+    let params = (user.id.get() as i64, timestamp_to_db(drank_at), amount_ml);
+    call(move |conn| {
+        conn.execute(
+            "INSERT INTO intake_events (user_id, drank_at, amount_ml) VALUES (?1, ?2, ?3)",
+            params,
+        )
+    })
+    .await?;
+
+    info!("Saved intake event of user {} to database", user.name);
+    Ok(())
+}
+
+/// Amounts of the intake events of a user since the given time
+pub async fn load_intake_amounts_since(
+    user: &User,
+    since: NaiveDateTime,
+) -> SqliteResult<Vec<Option<u32>>> {
+    let params = (user.id.get() as i64, timestamp_to_db(since));
+    call(move |conn| {
+        conn.prepare("SELECT amount_ml FROM intake_events WHERE user_id = ?1 AND drank_at >= ?2")?
+            .query_map(params, |row| row.get(0))?
+            .collect()
+    })
+    .await
+}
+
+pub async fn update_user_awaiting_acknowledgement(
+    user: &User,
+    awaiting_acknowledgement: bool,
+) -> SqliteResult<()> {
+    let params = (awaiting_acknowledgement, user.id.get() as i64);
+    call(move |conn| {
+        conn.execute(
+            "UPDATE users SET awaiting_acknowledgement = ?1 WHERE user_id = ?2",
+            params,
+        )
+    })
+    .await?;
+
+    Ok(())
+}
+
+pub async fn update_user_frequency(
+    user: &User,
+    frequency: ReminderFrequency,
+    adaptive: Option<AdaptiveBounds>,
+) -> SqliteResult<()> {
+    let params = (
+        frequency_to_db(frequency),
+        adaptive.map(|bounds| bounds.min_minutes),
+        adaptive.map(|bounds| bounds.max_minutes),
+        user.id.get() as i64,
+    );
+    call(move |conn| {
+        conn.execute(
+            "UPDATE users SET reminder_frequency = ?1, adaptive_min = ?2, adaptive_max = ?3 WHERE user_id = ?4",
+            params,
+        )
+    })
+    .await?;
+
+    info!("Updated frequency for user {} in database", user.name);
+    Ok(())
+}
+
+pub async fn update_user_presence_aware(user: &User, presence_aware: bool) -> SqliteResult<()> {
+    let params = (presence_aware, user.id.get() as i64);
+    call(move |conn| {
+        conn.execute(
+            "UPDATE users SET presence_aware = ?1 WHERE user_id = ?2",
+            params,
+        )
+    })
+    .await?;
+
     info!(
-        "Updated last reminded time for user {} in database",
+        "Updated presence awareness for user {} in database",
+        user.name
+    );
+    Ok(())
+}
+
+pub async fn update_user_voice_nudge(
+    user: &User,
+    voice_nudge_minutes: Option<u32>,
+) -> SqliteResult<()> {
+    let params = (voice_nudge_minutes, user.id.get() as i64);
+    call(move |conn| {
+        conn.execute(
+            "UPDATE users SET voice_nudge_minutes = ?1 WHERE user_id = ?2",
+            params,
+        )
+    })
+    .await?;
+
+    info!("Updated voice nudges for user {} in database", user.name);
+    Ok(())
+}
+
+pub async fn update_user_goal(
+    user: &User,
+    goal: Option<DailyGoal>,
+    frequency: ReminderFrequency,
+) -> SqliteResult<()> {
+    // A goal replaces the adaptive mode
+    let query = match goal {
+        Some(_) => {
+            "UPDATE users SET goal_ml = ?1, reminder_frequency = ?2, adaptive_min = NULL, adaptive_max = NULL WHERE user_id = ?3"
+        }
+        None => "UPDATE users SET goal_ml = ?1, reminder_frequency = ?2 WHERE user_id = ?3",
+    };
+    let params = (
+        goal.map(|goal| goal.target_ml),
+        frequency_to_db(frequency),
+        user.id.get() as i64,
+    );
+    call(move |conn| conn.execute(query, params)).await?;
+
+    info!("Updated daily goal for user {} in database", user.name);
+    Ok(())
+}
+
+pub async fn insert_adaptive_adjustment(
+    user: &User,
+    adjusted_at: NaiveDateTime,
+    previous_minutes: u32,
+    new_minutes: u32,
+    reason: AdjustmentReason,
+) -> SqliteResult<()> {
+    let params = (
+        user.id.get() as i64,
+        timestamp_to_db(adjusted_at),
+        previous_minutes,
+        new_minutes,
+        reason.to_db(),
+    );
+    call(move |conn| {
+        conn.execute(
+            "INSERT INTO adaptive_adjustments (user_id, adjusted_at, previous_minutes, new_minutes, reason)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params,
+        )
+    })
+    .await?;
+
+    info!(
+        "Saved adaptive adjustment of user {} to database",
+        user.name
+    );
+    Ok(())
+}
+
+pub async fn update_user_delivery_failures(
+    user: &User,
+    delivery_failures: u32,
+    suspended: bool,
+) -> SqliteResult<()> {
+    let params = (delivery_failures, suspended, user.id.get() as i64);
+    call(move |conn| {
+        conn.execute(
+            "UPDATE users SET delivery_failures = ?1, suspended = ?2 WHERE user_id = ?3",
+            params,
+        )
+    })
+    .await?;
+
+    info!(
+        "Updated delivery failures for user {} in database",
         user.name
     );
     Ok(())
 }
+
+pub async fn update_user_pause(user: &User, pause: Option<PauseState>) -> SqliteResult<()> {
+    let paused_until = match pause {
+        Some(PauseState::Until(until)) => Some(timestamp_to_db(until)),
+        _ => None,
+    };
+    let params = (pause.is_some(), paused_until, user.id.get() as i64);
+    call(move |conn| {
+        conn.execute(
+            "UPDATE users SET paused = ?1, paused_until = ?2 WHERE user_id = ?3",
+            params,
+        )
+    })
+    .await?;
+
+    info!("Updated pause for user {} in database", user.name);
+    Ok(())
+}
+
+pub async fn update_user_reminder_thread(
+    user: &User,
+    reminder_thread_id: Option<u64>,
+) -> SqliteResult<()> {
+    let params = (reminder_thread_id.map(|id| id as i64), user.id.get() as i64);
+    call(move |conn| {
+        conn.execute(
+            "UPDATE users SET reminder_thread_id = ?1 WHERE user_id = ?2",
+            params,
+        )
+    })
+    .await?;
+
+    info!("Updated reminder thread for user {} in database", user.name);
+    Ok(())
+}
+
+pub async fn insert_delivery_attempt(
+    user: &User,
+    attempted_at: NaiveDateTime,
+    attempt: u32,
+    target: DeliveryTarget,
+    outcome: DeliveryOutcome,
+    error: Option<String>,
+) -> SqliteResult<()> {
+    let params = (
+        user.id.get() as i64,
+        timestamp_to_db(attempted_at),
+        attempt,
+        outcome.to_db(),
+        error,
+        target.to_db(),
+    );
+    call(move |conn| {
+        conn.execute(
+            "INSERT INTO delivery_attempts (user_id, attempted_at, attempt, outcome, error, target)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params,
+        )
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Attempts made to deliver the reminders of a user, as (attempted_at, attempt, outcome), oldest
+/// first
+#[cfg(test)]
+pub async fn load_delivery_attempts(
+    user: &User,
+) -> SqliteResult<Vec<(NaiveDateTime, u32, String)>> {
+    let user_id = user.id.get() as i64;
+    call(move |conn| {
+        conn.prepare(
+            "SELECT attempted_at, attempt, outcome FROM delivery_attempts
+             WHERE user_id = ?1 ORDER BY attempted_at, attempt",
+        )?
+        .query_map([user_id], |row| {
+            let attempted_at: String = row.get(0)?;
+            Ok((
+                timestamp_from_db(&attempted_at).unwrap_or_default(),
+                row.get(1)?,
+                row.get(2)?,
+            ))
+        })?
+        .collect()
+    })
+    .await
+}
+
+#[derive(Debug, Clone)]
+pub struct AdaptiveAdjustment {
+    pub adjusted_at: NaiveDateTime,
+    pub previous_minutes: u32,
+    pub new_minutes: u32,
+    pub reason: AdjustmentReason,
+}
+
+/// Most recent adjustments first
+pub async fn load_adaptive_adjustments(
+    user: &User,
+    limit: u32,
+) -> SqliteResult<Vec<AdaptiveAdjustment>> {
+    let params = (user.id.get() as i64, limit);
+    call(move |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT adjusted_at, previous_minutes, new_minutes, reason FROM adaptive_adjustments
+             WHERE user_id = ?1 ORDER BY id DESC LIMIT ?2",
+        )?;
+
+        let adjustments = stmt
+            .query_map(params, |row| {
+                let adjusted_at: String = row.get(0)?;
+                let reason: String = row.get(3)?;
+                Ok((adjusted_at, row.get(1)?, row.get(2)?, reason))
+            })?
+            .filter_map(|result| match result {
+                Ok((adjusted_at, previous_minutes, new_minutes, reason)) => {
+                    Some(AdaptiveAdjustment {
+                        adjusted_at: timestamp_from_db(&adjusted_at)?,
+                        previous_minutes,
+                        new_minutes,
+                        reason: AdjustmentReason::from_db(&reason)?,
+                    })
+                }
+                Err(e) => {
+                    error!("Error loading adaptive adjustment from database: {}", e);
+                    None
+                }
+            })
+            .collect();
+
+        Ok(adjustments)
+    })
+    .await
+}
+
+/// Returns the id of the saved reminder
+pub async fn insert_one_off_reminder(
+    user: &User,
+    due_at: NaiveDateTime,
+    note: Option<&str>,
+) -> SqliteResult<i64> {
+    let params = (
+        user.id.get() as i64,
+        timestamp_to_db(due_at),
+        note.map(str::to_string),
+    );
+    let id = call(move |conn| {
+        conn.execute(
+            "INSERT INTO one_off_reminders (user_id, due_at, note) VALUES (?1, ?2, ?3)",
+            params,
+        )?;
+        Ok(conn.last_insert_rowid())
+    })
+    .await?;
+
+    info!("Saved one-off reminder of user {} to database", user.name);
+    Ok(id)
+}
+
+pub async fn remove_one_off_reminder(id: i64) -> SqliteResult<()> {
+    call(move |conn| {
+        conn.execute(
+            "DELETE FROM one_off_reminders WHERE id = ?1",
+            rusqlite::params![id],
+        )
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct StoredOneOffReminder {
+    pub id: i64,
+    pub user_id: u64,
+    pub due_at: NaiveDateTime,
+    pub note: Option<String>,
+}
+
+pub async fn load_one_off_reminders() -> SqliteResult<Vec<StoredOneOffReminder>> {
+    call(|conn| {
+        let mut stmt = conn.prepare("SELECT id, user_id, due_at, note FROM one_off_reminders")?;
+
+        let reminders = stmt
+            .query_map([], |row| {
+                let user_id: i64 = row.get(1)?;
+                let due_at: String = row.get(2)?;
+                Ok((row.get(0)?, user_id as u64, due_at, row.get(3)?))
+            })?
+            .filter_map(|result| match result {
+                Ok((id, user_id, due_at, note)) => Some(StoredOneOffReminder {
+                    id,
+                    user_id,
+                    due_at: timestamp_from_db(&due_at)?,
+                    note,
+                }),
+                Err(e) => {
+                    error!("Error loading one-off reminder from database: {}", e);
+                    None
+                }
+            })
+            .collect();
+
+        Ok(reminders)
+    })
+    .await
+}
+
+pub async fn save_voice_session(user: &User, session: &VoiceSession) -> SqliteResult<()> {
+    let params = (
+        user.id.get() as i64,
+        timestamp_to_db(session.started_at),
+        session.last_nudged_at.map(timestamp_to_db),
+        session.left_at.map(timestamp_to_db),
+    );
+    call(move |conn| {
+        conn.execute(
+            "INSERT OR REPLACE INTO voice_sessions (user_id, started_at, last_nudged_at, left_at) VALUES (?1, ?2, ?3, ?4)",
+            params,
+        )
+    })
+    .await?;
+
+    Ok(())
+}
+
+pub async fn remove_voice_session(user: &User) -> SqliteResult<()> {
+    let user_id = user.id.get() as i64;
+    call(move |conn| {
+        conn.execute(
+            "DELETE FROM voice_sessions WHERE user_id = ?1",
+            rusqlite::params![user_id],
+        )
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Voice sessions, by user id
+pub async fn load_voice_sessions() -> SqliteResult<Vec<(u64, VoiceSession)>> {
+    call(|conn| {
+        let mut stmt = conn
+            .prepare("SELECT user_id, started_at, last_nudged_at, left_at FROM voice_sessions")?;
+
+        let parse = |time: Option<String>| time.and_then(|time| timestamp_from_db(&time));
+        let sessions = stmt
+            .query_map([], |row| {
+                let user_id: i64 = row.get(0)?;
+                Ok((
+                    user_id as u64,
+                    row.get::<_, String>(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                ))
+            })?
+            .filter_map(|result| match result {
+                Ok((user_id, started_at, last_nudged_at, left_at)) => Some((
+                    user_id,
+                    VoiceSession {
+                        started_at: timestamp_from_db(&started_at)?,
+                        last_nudged_at: parse(last_nudged_at),
+                        left_at: parse(left_at),
+                    },
+                )),
+                Err(e) => {
+                    error!("Error loading voice session from database: {}", e);
+                    None
+                }
+            })
+            .collect();
+
+        Ok(sessions)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serenity::all::UserId;
+
+    use crate::{
+        adaptive::AdjustmentReason, dispatch::DeliveryOutcome, registry::ReminderFrequency,
+    };
+
+    fn user() -> User {
+        let mut user = User::default();
+        user.id = UserId::new(42);
+        user.name = "alice".to_string();
+        user
+    }
+
+    /// A time that no format dropping sub-seconds, or rounding them, would give back
+    fn time(time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S%.f").unwrap()
+    }
+
+    async fn stored_time(table: &str, column: &str) -> Option<NaiveDateTime> {
+        let query = format!("SELECT {column} FROM {table}");
+        let time: String = call(move |conn| conn.query_row(&query, [], |row| row.get(0)))
+            .await
+            .unwrap();
+        timestamp_from_db(&time)
+    }
+
+    async fn register(user: &User, last_reminded: NaiveDateTime, created_at: NaiveDateTime) {
+        let settings = UserSettings::new(ReminderFrequency::OneHour, Tz::Europe__Paris);
+        save_user_reminder(user, &settings, last_reminded, created_at)
+            .await
+            .unwrap();
+    }
+
+    async fn load_user(now: NaiveDateTime) -> StoredUser {
+        let mut users = load_user_reminders(now).await.unwrap();
+        users.remove(&UserData::from(&user())).unwrap()
+    }
+
+    #[test]
+    fn custom_frequencies_round_trip() {
+        let frequency = ReminderFrequency::custom(45).unwrap();
+        assert_eq!(frequency_to_db(frequency), "Custom:45");
+        assert_eq!(frequency_from_db("Custom:45"), Some(frequency));
+        assert_eq!(
+            frequency_from_db(&frequency_to_db(ReminderFrequency::ThreeHours)),
+            Some(ReminderFrequency::ThreeHours)
+        );
+    }
+
+    #[test]
+    fn custom_frequencies_out_of_bounds_are_rejected() {
+        assert_eq!(ReminderFrequency::custom(9), None);
+        assert_eq!(ReminderFrequency::custom(481), None);
+        assert_eq!(frequency_from_db("Custom:9"), None);
+        assert_eq!(frequency_from_db("Custom:481"), None);
+        assert_eq!(frequency_from_db("Custom:-45"), None);
+        assert_eq!(
+            frequency_from_db("Custom:10"),
+            Some(ReminderFrequency::Custom(10))
+        );
+        assert_eq!(
+            frequency_from_db("Custom:480"),
+            Some(ReminderFrequency::Custom(480))
+        );
+    }
+
+    #[test]
+    fn timestamps_sort_as_text_like_in_time() {
+        let times = [
+            time("2025-06-01 09:59:59.999999999"),
+            time("2025-06-01 10:00:00"),
+            time("2025-06-01 10:00:00.000000001"),
+            time("2025-06-01 10:00:00.1"),
+            time("2025-06-01 10:00:01"),
+            time("2025-12-31 23:59:59.5"),
+            time("2026-01-01 00:00:00"),
+        ];
+        let stored: Vec<String> = times.iter().copied().map(timestamp_to_db).collect();
+
+        let mut sorted = stored.clone();
+        sorted.sort();
+        assert_eq!(sorted, stored);
+        for (time, stored) in times.iter().zip(&stored) {
+            assert_eq!(timestamp_from_db(stored), Some(*time));
+        }
+    }
+
+    #[test]
+    fn timestamps_with_an_offset_are_read_in_utc() {
+        assert_eq!(
+            timestamp_from_db("2025-06-01T11:00:00.25+02:00"),
+            Some(time("2025-06-01 09:00:00.25"))
+        );
+        assert_eq!(timestamp_from_db("2025-06-01 09:00:00"), None);
+    }
+
+    #[tokio::test]
+    async fn last_reminded_and_created_at_round_trip() {
+        let _database = init_memory_database().await;
+        let (last_reminded, created_at) = (
+            time("2025-06-01 09:00:00.123456789"),
+            time("2025-05-01 08:30:00.000001"),
+        );
+        register(&user(), last_reminded, created_at).await;
+
+        let now = time("2025-06-02 12:00:00");
+        assert_eq!(load_user(now).await.last_reminded, last_reminded);
+        assert_eq!(stored_time("users", "created_at").await, Some(created_at));
+
+        let last_reminded = time("2025-06-01 10:00:00.5");
+        update_user_last_reminded(&user(), last_reminded)
+            .await
+            .unwrap();
+        assert_eq!(load_user(now).await.last_reminded, last_reminded);
+    }
+
+    #[tokio::test]
+    async fn snoozed_until_round_trips() {
+        let _database = init_memory_database().await;
+        register(
+            &user(),
+            time("2025-06-01 09:00:00"),
+            time("2025-06-01 09:00:00"),
+        )
+        .await;
+        let snoozed_until = time("2025-06-01 09:10:00.987654321");
+        update_user_snoozed_until(&user(), Some(snoozed_until))
+            .await
+            .unwrap();
+
+        let loaded = load_user(time("2025-06-01 09:05:00")).await;
+        assert_eq!(loaded.snoozed_until, Some(snoozed_until));
+    }
+
+    #[tokio::test]
+    async fn paused_until_round_trips() {
+        let _database = init_memory_database().await;
+        register(
+            &user(),
+            time("2025-06-01 09:00:00"),
+            time("2025-06-01 09:00:00"),
+        )
+        .await;
+        let paused_until = time("2025-06-01 13:37:00.000000042");
+        update_user_pause(&user(), Some(PauseState::Until(paused_until)))
+            .await
+            .unwrap();
+
+        let loaded = load_user(time("2025-06-01 09:05:00")).await;
+        assert_eq!(loaded.pause, Some(PauseState::Until(paused_until)));
+    }
+
+    #[tokio::test]
+    async fn drank_at_round_trips_and_bounds_the_intake() {
+        let _database = init_memory_database().await;
+        let drank_at = time("2025-06-01 09:59:59.999999999");
+        insert_intake_event(&user(), drank_at, Some(250))
+            .await
+            .unwrap();
+        insert_intake_event(&user(), time("2025-06-01 10:00:00"), None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            load_intake_amounts_since(&user(), drank_at).await.unwrap(),
+            vec![Some(250), None]
+        );
+        assert_eq!(
+            load_intake_amounts_since(&user(), time("2025-06-01 10:00:00"))
+                .await
+                .unwrap(),
+            vec![None]
+        );
+        assert!(
+            load_intake_amounts_since(&user(), time("2025-06-01 10:00:00.000000001"))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn adjusted_at_round_trips() {
+        let _database = init_memory_database().await;
+        let adjusted_at = time("2025-06-01 09:05:12.5");
+        insert_adaptive_adjustment(
+            &user(),
+            adjusted_at,
+            60,
+            75,
+            AdjustmentReason::QuickAcknowledgement,
+        )
+        .await
+        .unwrap();
+
+        let adjustments = load_adaptive_adjustments(&user(), 1).await.unwrap();
+        assert_eq!(adjustments.len(), 1);
+        assert_eq!(adjustments[0].adjusted_at, adjusted_at);
+    }
+
+    #[tokio::test]
+    async fn attempted_at_round_trips() {
+        let _database = init_memory_database().await;
+        let attempted_at = time("2025-06-01 09:00:00.000000999");
+        insert_delivery_attempt(
+            &user(),
+            attempted_at,
+            1,
+            DeliveryTarget::DirectMessage,
+            DeliveryOutcome::Delivered,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            stored_time("delivery_attempts", "attempted_at").await,
+            Some(attempted_at)
+        );
+    }
+
+    #[tokio::test]
+    async fn due_at_round_trips() {
+        let _database = init_memory_database().await;
+        let due_at = time("2025-06-03 18:30:00.75");
+        insert_one_off_reminder(&user(), due_at, Some("gourde"))
+            .await
+            .unwrap();
+
+        let reminders = load_one_off_reminders().await.unwrap();
+        assert_eq!(reminders.len(), 1);
+        assert_eq!(reminders[0].due_at, due_at);
+    }
+
+    #[tokio::test]
+    async fn voice_session_times_round_trip() {
+        let _database = init_memory_database().await;
+        let session = VoiceSession {
+            started_at: time("2025-06-01 20:00:00.123"),
+            last_nudged_at: Some(time("2025-06-01 21:00:00.456")),
+            left_at: Some(time("2025-06-01 21:30:00.789")),
+        };
+        save_voice_session(&user(), &session).await.unwrap();
+
+        assert_eq!(
+            load_voice_sessions().await.unwrap(),
+            vec![(user().id.get(), session)]
+        );
+    }
+
+    #[tokio::test]
+    async fn file_database_uses_wal_and_a_busy_timeout() {
+        let path = std::env::temp_dir().join(format!("discord-wal-{}.sqlite", std::process::id()));
+        let database = open_database(tokio_rusqlite::Connection::open(&path).await)
+            .await
+            .unwrap();
+
+        let (journal_mode, busy_timeout) = database
+            .call(|conn| {
+                let journal_mode: String =
+                    conn.pragma_query_value(None, "journal_mode", |row| row.get(0))?;
+                let busy_timeout: u64 =
+                    conn.pragma_query_value(None, "busy_timeout", |row| row.get(0))?;
+                Ok((journal_mode, busy_timeout))
+            })
+            .await
+            .unwrap();
+        database.close().await.unwrap();
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+
+        assert_eq!(journal_mode, "wal");
+        assert_eq!(busy_timeout, BUSY_TIMEOUT.as_millis() as u64);
+    }
+
+    #[tokio::test]
+    async fn queries_fail_once_the_database_is_closed() {
+        let _database = init_memory_database().await;
+        close_database().await.unwrap();
+
+        assert!(load_one_off_reminders().await.is_err());
+    }
+}
@@ -0,0 +1,174 @@
+use std::{env, fmt::Display};
+
+use serenity::{
+    Error as SerenityError,
+    all::{
+        ChannelId, ChannelType, CreateAllowedMentions, CreateMessage, CreateThread, Http,
+        Mentionable, Message, User,
+    },
+    http::HttpError,
+};
+use tracing::info;
+
+use crate::{
+    dispatch::{DISPATCHER, REQUESTS_PER_CHANNEL_MESSAGE, REQUESTS_PER_DM},
+    registry::{lookup_reminder_thread, update_user_reminder_thread},
+};
+
+/// Discord error code of a deleted channel or thread
+const UNKNOWN_CHANNEL_CODE: isize = 10003;
+
+/// Where the reminders of a user are posted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryTarget {
+    DirectMessage,
+    /// The reminder channel of the guild, with a mention of the user
+    GuildChannel,
+    /// A private thread of the reminder channel, created once per user
+    PrivateThread,
+}
+
+impl DeliveryTarget {
+    pub fn to_db(self) -> &'static str {
+        match self {
+            DeliveryTarget::DirectMessage => "DirectMessage",
+            DeliveryTarget::GuildChannel => "GuildChannel",
+            DeliveryTarget::PrivateThread => "PrivateThread",
+        }
+    }
+
+    pub fn from_db(target: &str) -> Option<Self> {
+        match target {
+            "DirectMessage" => Some(DeliveryTarget::DirectMessage),
+            "GuildChannel" => Some(DeliveryTarget::GuildChannel),
+            "PrivateThread" => Some(DeliveryTarget::PrivateThread),
+            _ => None,
+        }
+    }
+
+    /// Values of the slash command choices
+    pub fn from_choice(choice: &str) -> Option<Self> {
+        match choice {
+            "dm" => Some(DeliveryTarget::DirectMessage),
+            "channel" => Some(DeliveryTarget::GuildChannel),
+            "thread" => Some(DeliveryTarget::PrivateThread),
+            _ => None,
+        }
+    }
+
+    pub fn needs_reminder_channel(self) -> bool {
+        self != DeliveryTarget::DirectMessage
+    }
+}
+
+impl Display for DeliveryTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeliveryTarget::DirectMessage => write!(f, "en messages privés"),
+            DeliveryTarget::GuildChannel => write!(f, "dans le salon des rappels"),
+            DeliveryTarget::PrivateThread => write!(f, "dans un fil privé"),
+        }
+    }
+}
+
+/// `DISCORD_REMINDER_CHANNEL_ID`, the channel hosting reminders that aren't sent as DMs
+pub fn reminder_channel() -> Option<ChannelId> {
+    let Ok(Ok(channel_id)) = env::var("DISCORD_REMINDER_CHANNEL_ID").map(|it| it.parse::<u64>())
+    else {
+        return None;
+    };
+    Some(ChannelId::new(channel_id))
+}
+
+fn is_unknown_channel(why: &SerenityError) -> bool {
+    matches!(
+        why,
+        SerenityError::Http(HttpError::UnsuccessfulRequest(response))
+            if response.error.code == UNKNOWN_CHANNEL_CODE
+    )
+}
+
+/// Returns the private thread of the user, creating it the first time
+async fn reminder_thread(
+    http: &Http,
+    user: &User,
+    channel_id: ChannelId,
+) -> Result<ChannelId, SerenityError> {
+    if let Some(thread_id) = lookup_reminder_thread(user).await {
+        return Ok(thread_id);
+    }
+
+    let thread = DISPATCHER
+        .send(
+            REQUESTS_PER_CHANNEL_MESSAGE,
+            channel_id.create_thread(
+                http,
+                CreateThread::new(format!("💧 Rappels de {}", user.name))
+                    .kind(ChannelType::PrivateThread)
+                    .invitable(false),
+            ),
+        )
+        .await?;
+    DISPATCHER
+        .send(
+            REQUESTS_PER_CHANNEL_MESSAGE,
+            thread.id.add_thread_member(http, user.id),
+        )
+        .await?;
+
+    info!("Created the reminder thread of user {}", user.name);
+    update_user_reminder_thread(user, Some(thread.id)).await;
+    Ok(thread.id)
+}
+
+/// Posts a message in a guild channel, mentioning the user so that it gets notified
+async fn send_mention(
+    http: &Http,
+    user: &User,
+    channel_id: ChannelId,
+    content: &str,
+    message: CreateMessage,
+) -> Result<Message, SerenityError> {
+    let message = message
+        .content(format!("{} {content}", user.mention()))
+        .allowed_mentions(CreateAllowedMentions::new().users([user.id]));
+    DISPATCHER
+        .send(
+            REQUESTS_PER_CHANNEL_MESSAGE,
+            channel_id.send_message(http, message),
+        )
+        .await
+}
+
+/// Sends `message` with its `content` to the user through `target`
+pub async fn send_reminder(
+    http: &Http,
+    user: &User,
+    target: DeliveryTarget,
+    content: &str,
+    message: CreateMessage,
+) -> Result<Message, SerenityError> {
+    if target == DeliveryTarget::DirectMessage {
+        return DISPATCHER
+            .send(REQUESTS_PER_DM, user.dm(http, message.content(content)))
+            .await;
+    }
+
+    let channel_id = reminder_channel().ok_or(SerenityError::Other(
+        "No reminder channel has been configured",
+    ))?;
+    if target == DeliveryTarget::GuildChannel {
+        return send_mention(http, user, channel_id, content, message).await;
+    }
+
+    let thread_id = reminder_thread(http, user, channel_id).await?;
+    match send_mention(http, user, thread_id, content, message.clone()).await {
+        Err(why) if is_unknown_channel(&why) => {
+            // The thread got deleted, a new one replaces it
+            update_user_reminder_thread(user, None).await;
+            let thread_id = reminder_thread(http, user, channel_id).await?;
+            send_mention(http, user, thread_id, content, message).await
+        }
+        res => res,
+    }
+}
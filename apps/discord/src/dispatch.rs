@@ -0,0 +1,300 @@
+use std::{
+    future::Future,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use serenity::{
+    Error as SerenityError,
+    http::{HttpError, StatusCode},
+};
+use tokio::{sync::Semaphore, time::Instant};
+use tracing::warn;
+
+/// Amount of deliveries allowed to talk to Discord at the same time
+const MAX_CONCURRENT_DELIVERIES: usize = 8;
+
+/// Stays under Discord's global limit of 50 requests per second, leaving room for interactions
+const MAX_REQUESTS_PER_SECOND: u32 = 40;
+
+/// A DM costs two requests : opening the DM channel, then sending the message
+pub const REQUESTS_PER_DM: u32 = 2;
+
+/// Sending a message to a known guild channel or thread is a single request
+pub const REQUESTS_PER_CHANNEL_MESSAGE: u32 = 1;
+
+/// Pause applied to every delivery when a 429 makes it through serenity's own retries
+const RATE_LIMITED_BACKOFF: Duration = Duration::from_secs(5);
+
+pub static DISPATCHER: LazyLock<Dispatcher> = LazyLock::new(|| {
+    Dispatcher::new(
+        MAX_CONCURRENT_DELIVERIES,
+        Duration::from_secs(1) / MAX_REQUESTS_PER_SECOND,
+    )
+});
+
+/// Bounds the amount of concurrent deliveries and spaces their requests out.
+///
+/// Per-route buckets and the `retry-after` of 429 responses are already honoured by serenity's
+/// ratelimiter, which sleeps and retries on its own. The dispatcher keeps bursts of reminders
+/// away from the global limit, and slows everyone down if a 429 still surfaces.
+pub struct Dispatcher {
+    permits: Semaphore,
+    request_interval: Duration,
+    next_request: Mutex<Instant>,
+}
+
+impl Dispatcher {
+    fn new(max_concurrent: usize, request_interval: Duration) -> Self {
+        Self {
+            permits: Semaphore::new(max_concurrent),
+            request_interval,
+            next_request: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Reserves the next free request slots and waits for them
+    async fn pace(&self, requests: u32) {
+        let slot = {
+            let mut next_request = self.next_request.lock().unwrap_or_else(|e| e.into_inner());
+            let slot = (*next_request).max(Instant::now());
+            *next_request = slot + self.request_interval * requests;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+
+    /// Pushes every upcoming request back by `delay`
+    fn back_off(&self, delay: Duration) {
+        let mut next_request = self.next_request.lock().unwrap_or_else(|e| e.into_inner());
+        *next_request = (*next_request).max(Instant::now() + delay);
+    }
+
+    /// Runs a delivery request, made of `requests` calls to Discord, once a delivery slot is free
+    /// and the global pace allows it
+    pub async fn send<T>(
+        &self,
+        requests: u32,
+        request: impl Future<Output = Result<T, SerenityError>>,
+    ) -> Result<T, SerenityError> {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("the dispatcher semaphore is never closed");
+        self.pace(requests).await;
+
+        let res = request.await;
+        if let Err(why) = &res
+            && is_rate_limited(why)
+        {
+            warn!(
+                "Rate limited by Discord, delaying deliveries by {:?}",
+                RATE_LIMITED_BACKOFF
+            );
+            self.back_off(RATE_LIMITED_BACKOFF);
+        }
+        res
+    }
+}
+
+/// Whether a failed delivery is worth retrying
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryFailure {
+    /// Network errors, Discord outages and rate limits
+    Transient,
+    /// Closed DMs, blocked bot, unknown user or a rejected message
+    Permanent,
+}
+
+impl DeliveryFailure {
+    pub fn classify(why: &SerenityError) -> Self {
+        match why {
+            SerenityError::Http(HttpError::UnsuccessfulRequest(response)) => {
+                let status = response.status_code;
+                if status.is_server_error()
+                    || status == StatusCode::TOO_MANY_REQUESTS
+                    || status == StatusCode::REQUEST_TIMEOUT
+                {
+                    DeliveryFailure::Transient
+                } else {
+                    DeliveryFailure::Permanent
+                }
+            }
+            SerenityError::Http(HttpError::Request(_)) | SerenityError::Io(_) => {
+                DeliveryFailure::Transient
+            }
+            _ => DeliveryFailure::Permanent,
+        }
+    }
+}
+
+/// Outcome of a single delivery attempt, as saved in the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryOutcome {
+    Delivered,
+    Failed(DeliveryFailure),
+}
+
+impl DeliveryOutcome {
+    pub fn to_db(self) -> &'static str {
+        match self {
+            DeliveryOutcome::Delivered => "Delivered",
+            DeliveryOutcome::Failed(DeliveryFailure::Transient) => "TransientFailure",
+            DeliveryOutcome::Failed(DeliveryFailure::Permanent) => "PermanentFailure",
+        }
+    }
+}
+
+fn is_rate_limited(why: &SerenityError) -> bool {
+    matches!(
+        why,
+        SerenityError::Http(HttpError::UnsuccessfulRequest(response))
+            if response.status_code == StatusCode::TOO_MANY_REQUESTS
+    )
+}
+
+/// An error response from Discord, with the given HTTP status and JSON error code
+#[cfg(test)]
+pub async fn discord_error(status: u16, code: isize) -> SerenityError {
+    let response = http::Response::builder()
+        .status(status)
+        .body(format!(
+            r#"{{"code": {code}, "message": "Discord error {code}"}}"#
+        ))
+        .unwrap();
+    let response =
+        serenity::http::ErrorResponse::from_response(response.into(), reqwest::Method::POST).await;
+    SerenityError::Http(HttpError::UnsuccessfulRequest(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use tokio::task::JoinSet;
+
+    use super::*;
+
+    fn dispatcher() -> Arc<Dispatcher> {
+        Arc::new(Dispatcher::new(
+            MAX_CONCURRENT_DELIVERIES,
+            Duration::from_secs(1) / MAX_REQUESTS_PER_SECOND,
+        ))
+    }
+
+    /// Sends `count` deliveries of `requests` requests at once, each taking a second, and returns
+    /// when each of them started along with the most that were in flight together
+    async fn send_burst(
+        dispatcher: &Arc<Dispatcher>,
+        count: usize,
+        requests: u32,
+    ) -> (Vec<Duration>, usize) {
+        let start = Instant::now();
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let most_in_flight = Arc::new(AtomicUsize::new(0));
+        let mut deliveries = JoinSet::new();
+        for _ in 0..count {
+            let (dispatcher, in_flight, most_in_flight) = (
+                Arc::clone(dispatcher),
+                Arc::clone(&in_flight),
+                Arc::clone(&most_in_flight),
+            );
+            deliveries.spawn(async move {
+                dispatcher
+                    .send(requests, async {
+                        let started = start.elapsed();
+                        let now_in_flight = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                        most_in_flight.fetch_max(now_in_flight, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                        in_flight.fetch_sub(1, Ordering::SeqCst);
+                        Ok::<_, SerenityError>(started)
+                    })
+                    .await
+                    .unwrap()
+            });
+        }
+
+        let mut started = deliveries.join_all().await;
+        started.sort();
+        (started, most_in_flight.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn closed_dms_and_rejected_messages_are_permanent() {
+        // 50007 is "Cannot send messages to this user", 10013 "Unknown user"
+        for (status, code) in [(403, 50007), (404, 10013), (400, 50035), (401, 0)] {
+            assert_eq!(
+                DeliveryFailure::classify(&discord_error(status, code).await),
+                DeliveryFailure::Permanent,
+                "{status} / {code}"
+            );
+        }
+        assert_eq!(
+            DeliveryFailure::classify(&SerenityError::Other("unexpected")),
+            DeliveryFailure::Permanent
+        );
+    }
+
+    #[tokio::test]
+    async fn outages_rate_limits_and_network_errors_are_transient() {
+        for status in [500, 502, 503, 504, 429, 408] {
+            assert_eq!(
+                DeliveryFailure::classify(&discord_error(status, 0).await),
+                DeliveryFailure::Transient,
+                "{status}"
+            );
+        }
+        let reset = SerenityError::Io(std::io::Error::other("connection reset"));
+        assert_eq!(
+            DeliveryFailure::classify(&reset),
+            DeliveryFailure::Transient
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn deliveries_are_bounded_and_spaced_out() {
+        let (started, most_in_flight) = send_burst(&dispatcher(), 20, REQUESTS_PER_DM).await;
+        assert_eq!(most_in_flight, MAX_CONCURRENT_DELIVERIES);
+
+        // A DM is two requests, so 40 requests per second means a DM every 50 ms
+        let spacing = Duration::from_millis(50);
+        assert_eq!(started[1] - started[0], spacing);
+        assert_eq!(
+            started[MAX_CONCURRENT_DELIVERIES - 1],
+            spacing * (MAX_CONCURRENT_DELIVERIES as u32 - 1)
+        );
+        assert!(started.windows(2).all(|pair| pair[1] - pair[0] >= spacing));
+        // The ninth one waits for the first to be done
+        assert_eq!(started[MAX_CONCURRENT_DELIVERIES], Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn channel_messages_are_spaced_by_a_single_request() {
+        let (started, _) = send_burst(&dispatcher(), 4, REQUESTS_PER_CHANNEL_MESSAGE).await;
+        assert_eq!(started, [0, 25, 50, 75].map(Duration::from_millis).to_vec());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limits_delay_every_delivery() {
+        let dispatcher = dispatcher();
+        let res = dispatcher
+            .send(REQUESTS_PER_CHANNEL_MESSAGE, async {
+                Err::<(), _>(discord_error(429, 0).await)
+            })
+            .await;
+        assert!(res.is_err());
+
+        let (started, _) = send_burst(&dispatcher, 2, REQUESTS_PER_CHANNEL_MESSAGE).await;
+        assert_eq!(
+            started,
+            vec![
+                RATE_LIMITED_BACKOFF,
+                RATE_LIMITED_BACKOFF + Duration::from_millis(25)
+            ]
+        );
+    }
+}
@@ -0,0 +1,333 @@
+use chrono::{NaiveDateTime, TimeDelta, TimeZone, Timelike};
+use chrono_tz::Tz;
+use serenity::all::User;
+
+use crate::registry::{
+    MAX_CUSTOM_FREQUENCY_MINUTES, MIN_CUSTOM_FREQUENCY_MINUTES, ReminderFrequency, UserSettings,
+    lookup_intake_since,
+};
+use crate::schedule::{ActiveWindow, local_to_utc};
+
+pub const MIN_DAILY_GOAL_ML: u32 = 500;
+pub const MAX_DAILY_GOAL_ML: u32 = 5000;
+
+/// Volume of a glass, used to decide how many reminders a daily goal needs
+const GLASS_ML: u32 = 250;
+
+/// Amounts advised to the user are rounded to this volume
+const SERVING_STEP_ML: u32 = 50;
+
+/// Being behind by less than this is still considered on track
+const BEHIND_THRESHOLD_ML: u32 = 150;
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Volume a user wants to drink every day, spread over its active window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DailyGoal {
+    pub target_ml: u32,
+}
+
+impl DailyGoal {
+    /// Rejects targets outside of the allowed volumes
+    pub fn new(target_ml: u32) -> Option<Self> {
+        (MIN_DAILY_GOAL_ML..=MAX_DAILY_GOAL_ML)
+            .contains(&target_ml)
+            .then_some(Self { target_ml })
+    }
+
+    /// Spreads the target over the window, a glass at a time, within the custom frequency limits
+    pub fn pace(&self, window: ActiveWindow) -> GoalPace {
+        let window_minutes = window_minutes(window);
+        let glasses = self.target_ml.div_ceil(GLASS_ML).max(1);
+        let minutes = (window_minutes / glasses)
+            .clamp(MIN_CUSTOM_FREQUENCY_MINUTES, MAX_CUSTOM_FREQUENCY_MINUTES);
+        let reminders = (window_minutes / minutes).max(1);
+        GoalPace {
+            frequency: ReminderFrequency::Custom(minutes),
+            reminders,
+            serving_ml: round_serving(self.target_ml / reminders),
+        }
+    }
+}
+
+/// How often a daily goal is reminded, and how much to drink each time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GoalPace {
+    pub frequency: ReminderFrequency,
+    pub reminders: u32,
+    pub serving_ml: u32,
+}
+
+impl std::fmt::Display for GoalPace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} rappels d'environ {} ml, toutes les {}",
+            self.reminders, self.serving_ml, self.frequency
+        )
+    }
+}
+
+/// Part of the current goal day, the latest opening of the active window, already elapsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GoalDay {
+    /// Opening of the window, in UTC, from which intake counts towards the goal
+    pub starts_at: NaiveDateTime,
+    elapsed_minutes: u32,
+    window_minutes: u32,
+}
+
+impl GoalDay {
+    pub fn new(window: ActiveWindow, timezone: Tz, now: NaiveDateTime) -> Self {
+        let local = timezone.from_utc_datetime(&now).naive_local();
+        let since_start = (local.time().num_seconds_from_midnight() / 60 + MINUTES_PER_DAY
+            - window.start.num_seconds_from_midnight() / 60)
+            % MINUTES_PER_DAY;
+        let window_minutes = window_minutes(window);
+        Self {
+            starts_at: local_to_utc(timezone, local - TimeDelta::minutes(since_start as i64)),
+            elapsed_minutes: since_start.min(window_minutes),
+            window_minutes,
+        }
+    }
+}
+
+/// Compares the intake of the day with the pace of the goal, and tells how much to drink now so
+/// the rest of the day catches up
+pub fn progress_note(goal: DailyGoal, pace: GoalPace, day: GoalDay, drank_ml: u32) -> String {
+    let target_ml = goal.target_ml;
+    if drank_ml >= target_ml {
+        return format!(
+            "🎯 Objectif du jour atteint ({drank_ml}/{target_ml} ml), bravo ! Un petit verre reste le bienvenu."
+        );
+    }
+
+    let expected_ml = target_ml * day.elapsed_minutes / day.window_minutes.max(1);
+    let remaining_reminders =
+        (day.window_minutes - day.elapsed_minutes) / pace.frequency.minutes() + 1;
+    let serving_ml = round_serving((target_ml - drank_ml) / remaining_reminders);
+
+    let behind_ml = expected_ml.saturating_sub(drank_ml);
+    if behind_ml >= BEHIND_THRESHOLD_ML {
+        format!(
+            "Tu as {} ml de retard sur ton objectif ({drank_ml}/{target_ml} ml), bois un grand verre maintenant (environ {serving_ml} ml).",
+            round_serving(behind_ml)
+        )
+    } else {
+        format!("Objectif du jour : {drank_ml}/{target_ml} ml, bois environ {serving_ml} ml.")
+    }
+}
+
+/// Spreads what is left of the goal over what is left of the window, a glass at a time. Falling
+/// behind brings the reminders closer and drinking ahead spaces them out, within half and twice
+/// the planned interval.
+pub fn rebalanced_frequency(
+    goal: DailyGoal,
+    pace: GoalPace,
+    day: GoalDay,
+    drank_ml: u32,
+) -> ReminderFrequency {
+    let planned = pace.frequency.minutes();
+    let remaining_minutes = day.window_minutes - day.elapsed_minutes;
+    if remaining_minutes == 0 {
+        return pace.frequency;
+    }
+
+    let glasses = goal.target_ml.saturating_sub(drank_ml).div_ceil(GLASS_ML);
+    let minutes = remaining_minutes.checked_div(glasses).unwrap_or(u32::MAX);
+    ReminderFrequency::Custom(
+        minutes
+            .clamp(planned / 2, planned * 2)
+            .clamp(MIN_CUSTOM_FREQUENCY_MINUTES, MAX_CUSTOM_FREQUENCY_MINUTES),
+    )
+}
+
+/// What the user drank since the goal day started, acknowledgements without an amount counting as
+/// the planned serving
+pub async fn drank_today(user: &User, pace: GoalPace, day: GoalDay) -> u32 {
+    lookup_intake_since(user, day.starts_at)
+        .await
+        .into_iter()
+        .map(|amount_ml| amount_ml.unwrap_or(pace.serving_ml))
+        .sum()
+}
+
+/// Tells a user with a daily goal where it stands, and how much to drink to catch up
+pub async fn goal_note(user: &User, settings: &UserSettings, now: NaiveDateTime) -> Option<String> {
+    let goal = settings.goal?;
+    let window = settings.active_window?;
+    let pace = goal.pace(window);
+    let day = GoalDay::new(window, settings.timezone, now);
+
+    let drank_ml = drank_today(user, pace, day).await;
+    // The reminders left are counted at the current, rebalanced, interval
+    let current = GoalPace {
+        frequency: settings.frequency,
+        ..pace
+    };
+    Some(progress_note(goal, current, day, drank_ml))
+}
+
+/// Windows ending before they start span over midnight
+fn window_minutes(window: ActiveWindow) -> u32 {
+    let start = window.start.num_seconds_from_midnight() / 60;
+    let end = window.end.num_seconds_from_midnight() / 60;
+    (end + MINUTES_PER_DAY - start - 1) % MINUTES_PER_DAY + 1
+}
+
+fn round_serving(ml: u32) -> u32 {
+    ((ml + SERVING_STEP_ML / 2) / SERVING_STEP_ML * SERVING_STEP_ML).max(SERVING_STEP_ML)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveTime;
+
+    use super::*;
+
+    fn window(start: &str, end: &str) -> ActiveWindow {
+        let start = NaiveTime::parse_from_str(start, "%H:%M").unwrap();
+        let end = NaiveTime::parse_from_str(end, "%H:%M").unwrap();
+        ActiveWindow::new(start, end).unwrap()
+    }
+
+    fn utc(time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn goal(target_ml: u32) -> DailyGoal {
+        DailyGoal::new(target_ml).unwrap()
+    }
+
+    #[test]
+    fn goals_are_spread_a_glass_at_a_time() {
+        assert_eq!(
+            goal(2000).pace(window("09:00", "21:00")),
+            GoalPace {
+                frequency: ReminderFrequency::Custom(90),
+                reminders: 8,
+                serving_ml: 250,
+            }
+        );
+        assert!(DailyGoal::new(MIN_DAILY_GOAL_ML - 1).is_none());
+        assert!(DailyGoal::new(MAX_DAILY_GOAL_ML + 1).is_none());
+    }
+
+    #[test]
+    fn pace_stays_within_the_frequency_limits() {
+        // 20 glasses in an hour would be every 3 minutes
+        assert_eq!(
+            goal(5000).pace(window("09:00", "10:00")),
+            GoalPace {
+                frequency: ReminderFrequency::Custom(MIN_CUSTOM_FREQUENCY_MINUTES),
+                reminders: 6,
+                serving_ml: 850,
+            }
+        );
+        // 2 glasses over a whole day would be every 12 hours
+        assert_eq!(
+            goal(500).pace(window("06:00", "05:59")),
+            GoalPace {
+                frequency: ReminderFrequency::Custom(MAX_CUSTOM_FREQUENCY_MINUTES),
+                reminders: 2,
+                serving_ml: 250,
+            }
+        );
+    }
+
+    #[test]
+    fn users_behind_are_told_to_catch_up() {
+        let (goal, window) = (goal(2000), window("09:00", "21:00"));
+        // Halfway through the window, 1000 ml were expected
+        let day = GoalDay::new(window, Tz::UTC, utc("2025-06-02 15:00"));
+
+        // The 1400 ml left are spread over the 5 remaining reminders
+        assert_eq!(
+            progress_note(goal, goal.pace(window), day, 600),
+            "Tu as 400 ml de retard sur ton objectif (600/2000 ml), bois un grand verre maintenant (environ 300 ml)."
+        );
+        assert_eq!(
+            progress_note(goal, goal.pace(window), day, 950),
+            "Objectif du jour : 950/2000 ml, bois environ 200 ml."
+        );
+    }
+
+    #[test]
+    fn reached_goals_are_celebrated() {
+        let (goal, window) = (goal(2000), window("09:00", "21:00"));
+        let day = GoalDay::new(window, Tz::UTC, utc("2025-06-02 15:00"));
+
+        assert!(progress_note(goal, goal.pace(window), day, 2100).starts_with("🎯"));
+    }
+
+    #[test]
+    fn goal_days_span_over_midnight() {
+        let (goal, window) = (goal(1000), window("22:00", "02:00"));
+        assert_eq!(goal.pace(window).frequency, ReminderFrequency::Custom(60));
+
+        // At 01:00 the day started at 22:00 the evening before, 3 of its 4 hours are gone
+        let day = GoalDay::new(window, Tz::UTC, utc("2025-06-03 01:00"));
+        assert_eq!(day.starts_at, utc("2025-06-02 22:00"));
+        assert_eq!(
+            progress_note(goal, goal.pace(window), day, 0),
+            "Tu as 750 ml de retard sur ton objectif (0/1000 ml), bois un grand verre maintenant (environ 500 ml)."
+        );
+    }
+
+    #[test]
+    fn time_outside_the_window_counts_as_the_whole_window() {
+        let (goal, window) = (goal(1000), window("22:00", "02:00"));
+
+        // Past the end of the window the whole goal is expected, with a single reminder left
+        let day = GoalDay::new(window, Tz::UTC, utc("2025-06-03 12:00"));
+        assert_eq!(day.starts_at, utc("2025-06-02 22:00"));
+        assert_eq!(
+            progress_note(goal, goal.pace(window), day, 800),
+            "Tu as 200 ml de retard sur ton objectif (800/1000 ml), bois un grand verre maintenant (environ 200 ml)."
+        );
+    }
+
+    #[test]
+    fn reminders_are_rebalanced_on_what_is_left() {
+        let (goal, window) = (goal(2000), window("09:00", "21:00"));
+        let pace = goal.pace(window);
+        // Halfway through the window, 6 hours are left
+        let day = GoalDay::new(window, Tz::UTC, utc("2025-06-02 15:00"));
+
+        for (drank_ml, minutes) in [
+            // On track, 4 glasses in 6 hours keep the planned 90 minutes
+            (1000, 90),
+            (500, 60),
+            // Far behind or ahead, the interval stays within half and twice the planned one
+            (0, 45),
+            (1500, 180),
+            (2000, 180),
+        ] {
+            assert_eq!(
+                rebalanced_frequency(goal, pace, day, drank_ml),
+                ReminderFrequency::Custom(minutes),
+                "{drank_ml} ml"
+            );
+        }
+    }
+
+    #[test]
+    fn days_over_go_back_to_the_planned_pace() {
+        let (goal, window) = (goal(2000), window("09:00", "21:00"));
+        let pace = goal.pace(window);
+        let day = GoalDay::new(window, Tz::UTC, utc("2025-06-02 22:00"));
+        assert_eq!(rebalanced_frequency(goal, pace, day, 0), pace.frequency);
+    }
+
+    #[test]
+    fn goal_days_start_at_the_local_opening() {
+        // 09:00 in Paris is 07:00 UTC in summer
+        let day = GoalDay::new(
+            window("09:00", "21:00"),
+            Tz::Europe__Paris,
+            utc("2025-06-02 10:00"),
+        );
+        assert_eq!(day.starts_at, utc("2025-06-02 07:00"));
+    }
+}
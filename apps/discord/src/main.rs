@@ -2,27 +2,54 @@ use std::{env, sync::Arc};
 
 use serenity::{
     all::{
-        CreateInteractionResponse, CreateInteractionResponseMessage, GuildId, Interaction, Ready,
+        CreateInteractionResponse, CreateInteractionResponseMessage, Guild, GuildId, Interaction,
+        Presence, Ready, VoiceState,
     },
     async_trait,
     prelude::*,
 };
-use tokio::spawn;
-use tokio_schedule::{Job, every};
+use tokio::{
+    spawn,
+    sync::{
+        mpsc::{UnboundedSender, unbounded_channel},
+        oneshot,
+    },
+};
 use tracing::{error, info};
 
 use crate::{
-    database::init_database, logging::init_logging_system, registry::load_users_from_database,
-    reminder::walk_reminders,
+    catch_up::{CatchUpPolicy, apply_catch_up_policy},
+    clock::{Clock, SystemClock},
+    database::{close_database, init_database},
+    logging::init_logging_system,
+    one_off::load_one_off_reminders_from_database,
+    presence::{PRESENCES, PresenceEvent, presence_return_delay, track_presences},
+    registry::{load_users_from_database, release_deferred_reminder},
+    reminder::run_reminders,
+    voice::{load_voice_sessions_from_database, sync_guild_voice_states, update_voice_state},
 };
 
+mod adaptive;
 mod buttons;
+mod catch_up;
+mod clock;
 mod commands;
 mod data;
 mod database;
+mod delivery;
+mod dispatch;
+mod goal;
 mod logging;
+mod migrations;
+mod one_off;
+mod presence;
 mod registry;
 mod reminder;
+mod schedule;
+mod scheduler;
+#[cfg(test)]
+mod simulation;
+mod voice;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -38,35 +65,120 @@ async fn main() -> anyhow::Result<()> {
     let token = env::var("DISCORD_BOT_TOKEN")?;
     info!("Discord bot token has been found, not checked tho.");
 
-    let intents = GatewayIntents::GUILD_MESSAGES
+    // Presences are a privileged intent, to enable on the bot application page
+    let intents = GatewayIntents::GUILDS
+        | GatewayIntents::GUILD_MESSAGES
+        | GatewayIntents::GUILD_PRESENCES
+        | GatewayIntents::GUILD_VOICE_STATES
         | GatewayIntents::DIRECT_MESSAGES
         | GatewayIntents::MESSAGE_CONTENT;
 
+    let (presences, presence_events) = unbounded_channel();
     let mut client = Client::builder(&token, intents)
-        .event_handler(Handler)
+        .event_handler(Handler { presences })
         .await?;
     info!("Client has been prepared");
 
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+
     // Load existing users from database
-    load_users_from_database(&client.http).await;
+    load_users_from_database(&client.http, clock.now()).await;
     info!("Loaded existing users from database");
 
-    let cache_http = Arc::clone(&client.http);
-    let every_1_min = every(1)
-        .minutes()
-        .perform(move || walk_reminders(cache_http.clone()));
-    let _handle = spawn(every_1_min);
-    info!("Walk reminder task has been started");
+    load_one_off_reminders_from_database(&client.http).await;
+    info!("Loaded pending one-off reminders from database");
+
+    load_voice_sessions_from_database().await;
+    info!("Loaded voice sessions from database");
+
+    apply_catch_up_policy(CatchUpPolicy::from_env(), clock.now()).await;
+
+    let (stop_reminders, reminders_stopping) = oneshot::channel();
+    let reminders = spawn(run_reminders(
+        Arc::clone(&client.http),
+        Arc::clone(&clock),
+        async {
+            reminders_stopping.await.ok();
+        },
+    ));
+    info!("Reminder task has been started");
+
+    let _presence_handle = spawn(track_presences(&PRESENCES, presence_events, move |user| {
+        let not_before = clock.now() + presence_return_delay();
+        spawn(async move { release_deferred_reminder(&user, not_before).await });
+    }));
+    info!("Presence tracking task has been started");
+
+    let shard_manager = Arc::clone(&client.shard_manager);
+    spawn(async move {
+        shutdown_signal().await;
+
+        // Reminders in flight are recorded before the gateway goes away
+        stop_reminders.send(()).ok();
+        if let Err(why) = reminders.await {
+            error!("The reminder task failed : {}", why);
+        }
+        info!("Closing the shards");
+        shard_manager.shutdown_all().await;
+    });
 
     info!("Starting client...");
     if let Err(reason) = client.start_autosharded().await {
         error!("Client error while starting : {:?}", reason);
     }
 
+    // Events handled until the shards closed may still have written to the database
+    if let Err(e) = close_database().await {
+        error!("Failed to close the database: {}", e);
+    }
+    info!("Shutdown complete");
+
     Ok(())
 }
 
-struct Handler;
+/// Resolves once the process is asked to stop, with Ctrl+C or SIGTERM
+async fn shutdown_signal() {
+    let interrupt = async {
+        if let Err(why) = tokio::signal::ctrl_c().await {
+            error!("Cannot listen for Ctrl+C : {}", why);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(why) => {
+                error!("Cannot listen for SIGTERM : {}", why);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => info!("Received Ctrl+C, shutting down"),
+        _ = terminate => info!("Received SIGTERM, shutting down"),
+    }
+}
+
+struct Handler {
+    /// Feeds the presence tracking task
+    presences: UnboundedSender<PresenceEvent>,
+}
+
+impl Handler {
+    fn track_presence(&self, event: PresenceEvent) {
+        if let Err(why) = self.presences.send(event) {
+            error!("Presence tracking task is gone : {}", why);
+        }
+    }
+}
 
 #[async_trait]
 impl EventHandler for Handler {
@@ -86,6 +198,17 @@ impl EventHandler for Handler {
                 vec![
                     commands::register::register(),
                     commands::unregister::register(),
+                    commands::timezone::register(),
+                    commands::window::register(),
+                    commands::schedule::register(),
+                    commands::adaptive::register(),
+                    commands::pause::register(),
+                    commands::resume::register(),
+                    commands::vacation::register(),
+                    commands::remindme::register(),
+                    commands::goal::register(),
+                    commands::presence::register(),
+                    commands::voice::register(),
                 ],
             )
             .await;
@@ -93,7 +216,60 @@ impl EventHandler for Handler {
         info!("I now have the following guild slash commands : {commands:?}");
     }
 
+    async fn guild_create(&self, _ctx: Context, guild: Guild, _is_new: Option<bool>) {
+        info!(
+            "Received {} presences of guild {}",
+            guild.presences.len(),
+            guild.name
+        );
+        self.track_presence(PresenceEvent::snapshot(guild.presences.values()));
+
+        let voice_states = guild
+            .voice_states
+            .values()
+            .map(|state| (state.user_id, state.channel_id))
+            .collect();
+        sync_guild_voice_states(
+            guild.id,
+            guild.afk_metadata.map(|afk| afk.afk_channel_id),
+            voice_states,
+            chrono::Utc::now().naive_utc(),
+        )
+        .await;
+    }
+
+    async fn presence_update(&self, _ctx: Context, new_data: Presence) {
+        self.track_presence(PresenceEvent::from(&new_data));
+    }
+
+    async fn voice_state_update(&self, _ctx: Context, _old: Option<VoiceState>, new: VoiceState) {
+        update_voice_state(
+            new.user_id,
+            new.guild_id,
+            new.channel_id,
+            chrono::Utc::now().naive_utc(),
+        )
+        .await;
+    }
+
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        if let Interaction::Autocomplete(command) = &interaction {
+            if let Err(why) = commands::timezone::autocomplete(&ctx, command).await {
+                error!("The timezone autocompletion failed : {}", why);
+            }
+            return;
+        }
+
+        // Buttons of the /register flow are handled by their own collector
+        if let Interaction::Component(component) = &interaction {
+            if buttons::reminder_buttons::is_reminder_component(&component.data.custom_id)
+                && let Err(why) = buttons::reminder_buttons::run(&ctx, component).await
+            {
+                error!("The reminder component failed : {}", why);
+            }
+            return;
+        }
+
         if let Interaction::Command(command) = interaction {
             let content = match command.data.name.as_str() {
                 "register" => {
@@ -110,6 +286,83 @@ impl EventHandler for Handler {
                     }
                     None
                 }
+                "timezone" => {
+                    if let Err(why) = commands::timezone::run(&ctx, &command).await {
+                        error!("The timezone command failed : {}", why);
+                        return;
+                    }
+                    None
+                }
+                "window" => {
+                    if let Err(why) = commands::window::run(&ctx, &command).await {
+                        error!("The window command failed : {}", why);
+                        return;
+                    }
+                    None
+                }
+                "schedule" => {
+                    if let Err(why) = commands::schedule::run(&ctx, &command).await {
+                        error!("The schedule command failed : {}", why);
+                        return;
+                    }
+                    None
+                }
+                "adaptive" => {
+                    if let Err(why) = commands::adaptive::run(&ctx, &command).await {
+                        error!("The adaptive command failed : {}", why);
+                        return;
+                    }
+                    None
+                }
+                "pause" => {
+                    if let Err(why) = commands::pause::run(&ctx, &command).await {
+                        error!("The pause command failed : {}", why);
+                        return;
+                    }
+                    None
+                }
+                "resume" => {
+                    if let Err(why) = commands::resume::run(&ctx, &command).await {
+                        error!("The resume command failed : {}", why);
+                        return;
+                    }
+                    None
+                }
+                "vacation" => {
+                    if let Err(why) = commands::vacation::run(&ctx, &command).await {
+                        error!("The vacation command failed : {}", why);
+                        return;
+                    }
+                    None
+                }
+                "remindme" => {
+                    if let Err(why) = commands::remindme::run(&ctx, &command).await {
+                        error!("The remindme command failed : {}", why);
+                        return;
+                    }
+                    None
+                }
+                "goal" => {
+                    if let Err(why) = commands::goal::run(&ctx, &command).await {
+                        error!("The goal command failed : {}", why);
+                        return;
+                    }
+                    None
+                }
+                "presence" => {
+                    if let Err(why) = commands::presence::run(&ctx, &command).await {
+                        error!("The presence command failed : {}", why);
+                        return;
+                    }
+                    None
+                }
+                "voice" => {
+                    if let Err(why) = commands::voice::run(&ctx, &command).await {
+                        error!("The voice command failed : {}", why);
+                        return;
+                    }
+                    None
+                }
                 _ => Some("This command is not implemented :(".to_string()),
             };
 
@@ -0,0 +1,590 @@
+use chrono::{NaiveDateTime, SecondsFormat};
+use rusqlite::{Connection, Result as SqliteResult};
+use tracing::{info, warn};
+
+/// A change of the database schema, applied once in its own transaction
+pub struct Migration {
+    pub description: &'static str,
+    pub apply: fn(&Connection) -> SqliteResult<()>,
+}
+
+/// Every migration in order, the schema version of a database is the amount of migrations it
+/// went through. Released migrations must never change, only new ones be appended.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        description: "Create the users table",
+        apply: create_users_table,
+    },
+    Migration {
+        description: "Add the settings and tables released before versioned migrations",
+        apply: add_unversioned_schema,
+    },
+    Migration {
+        description: "Store timestamps as RFC 3339 in UTC, with nanoseconds",
+        apply: convert_timestamps_to_rfc3339,
+    },
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum MigrationError {
+    #[error(
+        "the database schema is at version {found}, newer than the version {supported} known by this binary"
+    )]
+    NewerSchema { found: u32, supported: u32 },
+    #[error("migration to version {version} ({description}) failed: {source}")]
+    Failed {
+        version: u32,
+        description: &'static str,
+        source: rusqlite::Error,
+    },
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+pub fn schema_version(conn: &Connection) -> SqliteResult<u32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+/// Brings the database up to the latest schema
+pub fn migrate(conn: &mut Connection) -> Result<(), MigrationError> {
+    apply_migrations(conn, MIGRATIONS)
+}
+
+/// Applies the migrations the database hasn't been through yet. A failed migration is rolled
+/// back, leaving the database at the version of the previous one.
+fn apply_migrations(conn: &mut Connection, migrations: &[Migration]) -> Result<(), MigrationError> {
+    let supported = migrations.len() as u32;
+    let found = schema_version(conn)?;
+    if found > supported {
+        return Err(MigrationError::NewerSchema { found, supported });
+    }
+
+    for (version, migration) in (1..).zip(migrations).skip(found as usize) {
+        let failed = |source| MigrationError::Failed {
+            version,
+            description: migration.description,
+            source,
+        };
+        let tx = conn.transaction()?;
+        (migration.apply)(&tx).map_err(failed)?;
+        tx.pragma_update(None, "user_version", version)
+            .map_err(failed)?;
+        tx.commit().map_err(failed)?;
+        info!(
+            "Migrated the database to version {} : {}",
+            version, migration.description
+        );
+    }
+
+    Ok(())
+}
+
+/// The schema of the first release. Databases created before versioned migrations already have
+/// it, hence the `IF NOT EXISTS` here and in the next migration.
+fn create_users_table(conn: &Connection) -> SqliteResult<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS users (
+            user_id INTEGER PRIMARY KEY,
+            username TEXT NOT NULL,
+            reminder_frequency TEXT NOT NULL,
+            last_reminded TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+const SCHEDULE_PROFILES_TABLE: &str = "CREATE TABLE IF NOT EXISTS schedule_profiles (
+    user_id INTEGER NOT NULL,
+    weekday INTEGER NOT NULL,
+    enabled INTEGER NOT NULL,
+    reminder_frequency TEXT,
+    active_start TEXT,
+    active_end TEXT,
+    PRIMARY KEY (user_id, weekday)
+)";
+
+const INTAKE_EVENTS_TABLE: &str = "CREATE TABLE IF NOT EXISTS intake_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    drank_at TEXT NOT NULL,
+    amount_ml INTEGER
+)";
+
+const ADAPTIVE_ADJUSTMENTS_TABLE: &str = "CREATE TABLE IF NOT EXISTS adaptive_adjustments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    adjusted_at TEXT NOT NULL,
+    previous_minutes INTEGER NOT NULL,
+    new_minutes INTEGER NOT NULL,
+    reason TEXT NOT NULL
+)";
+
+const DELIVERY_ATTEMPTS_TABLE: &str = "CREATE TABLE IF NOT EXISTS delivery_attempts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    attempted_at TEXT NOT NULL,
+    attempt INTEGER NOT NULL,
+    outcome TEXT NOT NULL,
+    error TEXT,
+    target TEXT NOT NULL DEFAULT 'DirectMessage'
+)";
+
+const ABSENCES_TABLE: &str = "CREATE TABLE IF NOT EXISTS absences (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    start_date TEXT NOT NULL,
+    end_date TEXT NOT NULL
+)";
+
+const ONE_OFF_REMINDERS_TABLE: &str = "CREATE TABLE IF NOT EXISTS one_off_reminders (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    due_at TEXT NOT NULL,
+    note TEXT
+)";
+
+const VOICE_SESSIONS_TABLE: &str = "CREATE TABLE IF NOT EXISTS voice_sessions (
+    user_id INTEGER PRIMARY KEY,
+    started_at TEXT NOT NULL,
+    last_nudged_at TEXT,
+    left_at TEXT
+)";
+
+/// Columns added to existing tables before versioned migrations, with their definition
+const UNVERSIONED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("users", "timezone", "TEXT NOT NULL DEFAULT 'UTC'"),
+    ("users", "active_start", "TEXT"),
+    ("users", "active_end", "TEXT"),
+    ("users", "snoozed_until", "TEXT"),
+    ("users", "adaptive_min", "INTEGER"),
+    ("users", "adaptive_max", "INTEGER"),
+    (
+        "users",
+        "awaiting_acknowledgement",
+        "INTEGER NOT NULL DEFAULT 0",
+    ),
+    ("users", "delivery_failures", "INTEGER NOT NULL DEFAULT 0"),
+    ("users", "suspended", "INTEGER NOT NULL DEFAULT 0"),
+    (
+        "users",
+        "delivery_target",
+        "TEXT NOT NULL DEFAULT 'DirectMessage'",
+    ),
+    ("users", "fallback_target", "TEXT"),
+    ("users", "reminder_thread_id", "INTEGER"),
+    ("users", "paused", "INTEGER NOT NULL DEFAULT 0"),
+    ("users", "paused_until", "TEXT"),
+    ("users", "cron_expression", "TEXT"),
+    ("users", "goal_ml", "INTEGER"),
+    ("users", "presence_aware", "INTEGER NOT NULL DEFAULT 0"),
+    ("users", "voice_nudge_minutes", "INTEGER"),
+    (
+        "delivery_attempts",
+        "target",
+        "TEXT NOT NULL DEFAULT 'DirectMessage'",
+    ),
+];
+
+/// Those databases were upgraded at startup by adding whatever was missing, so they may be
+/// anywhere between the first release and this migration
+fn add_unversioned_schema(conn: &Connection) -> SqliteResult<()> {
+    conn.execute(SCHEDULE_PROFILES_TABLE, [])?;
+    conn.execute(INTAKE_EVENTS_TABLE, [])?;
+    conn.execute(ADAPTIVE_ADJUSTMENTS_TABLE, [])?;
+    conn.execute(DELIVERY_ATTEMPTS_TABLE, [])?;
+    conn.execute(ABSENCES_TABLE, [])?;
+    conn.execute(ONE_OFF_REMINDERS_TABLE, [])?;
+    conn.execute(VOICE_SESSIONS_TABLE, [])?;
+
+    for (table, column, definition) in UNVERSIONED_COLUMNS {
+        if !table_columns(conn, table)?
+            .iter()
+            .any(|existing| existing == column)
+        {
+            conn.execute(
+                &format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"),
+                [],
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Every timestamp column, written with `NaiveDateTime::to_string` before the conversion
+const TIMESTAMP_COLUMNS: &[(&str, &str)] = &[
+    ("users", "last_reminded"),
+    ("users", "created_at"),
+    ("users", "snoozed_until"),
+    ("users", "paused_until"),
+    ("intake_events", "drank_at"),
+    ("adaptive_adjustments", "adjusted_at"),
+    ("delivery_attempts", "attempted_at"),
+    ("one_off_reminders", "due_at"),
+    ("voice_sessions", "started_at"),
+    ("voice_sessions", "last_nudged_at"),
+    ("voice_sessions", "left_at"),
+];
+
+/// Output of `NaiveDateTime::to_string`, fractional seconds only being there when not zero
+const LEGACY_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+
+/// The legacy format was read back with a format missing the fractional seconds, so most
+/// timestamps were lost on reload. Values that can't be read are left as they are.
+fn convert_timestamps_to_rfc3339(conn: &Connection) -> SqliteResult<()> {
+    for (table, column) in TIMESTAMP_COLUMNS {
+        let times = conn
+            .prepare(&format!(
+                "SELECT rowid, {column} FROM {table} WHERE {column} IS NOT NULL"
+            ))?
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<SqliteResult<Vec<_>>>()?;
+
+        let mut update = conn.prepare(&format!(
+            "UPDATE {table} SET {column} = ?1 WHERE rowid = ?2"
+        ))?;
+        for (rowid, time) in times {
+            match NaiveDateTime::parse_from_str(&time, LEGACY_TIMESTAMP_FORMAT) {
+                Ok(time) => {
+                    let time = time.and_utc().to_rfc3339_opts(SecondsFormat::Nanos, true);
+                    update.execute(rusqlite::params![time, rowid])?;
+                }
+                Err(_) => warn!(
+                    "Cannot read {} '{}' of row {} in {}, leaving it as is",
+                    column, time, rowid, table
+                ),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn table_columns(conn: &Connection, table: &str) -> SqliteResult<Vec<String>> {
+    conn.prepare(&format!("PRAGMA table_info({table})"))?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LATEST_VERSION: u32 = MIGRATIONS.len() as u32;
+
+    /// The database as created by the first release
+    fn baseline_database() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS users (
+                user_id INTEGER PRIMARY KEY,
+                username TEXT NOT NULL,
+                reminder_frequency TEXT NOT NULL,
+                last_reminded TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO users (user_id, username, reminder_frequency, last_reminded, created_at)
+             VALUES (42, 'alice', 'OneHour', '2025-06-01 09:00:00', '2025-05-01 08:30:00.123456')",
+            [],
+        )
+        .unwrap();
+        conn
+    }
+
+    fn tables(conn: &Connection) -> Vec<String> {
+        conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<SqliteResult<_>>()
+            .unwrap()
+    }
+
+    /// Tables and their columns, to compare two schemas
+    fn schema(conn: &Connection) -> Vec<(String, Vec<String>)> {
+        tables(conn)
+            .into_iter()
+            .map(|table| {
+                let columns = table_columns(conn, &table).unwrap();
+                (table, columns)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn new_databases_get_the_latest_schema() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+
+        assert_eq!(schema_version(&conn).unwrap(), LATEST_VERSION);
+        let mut tables = tables(&conn);
+        tables.retain(|table| table != "sqlite_sequence");
+        assert_eq!(
+            tables,
+            vec![
+                "absences",
+                "adaptive_adjustments",
+                "delivery_attempts",
+                "intake_events",
+                "one_off_reminders",
+                "schedule_profiles",
+                "users",
+                "voice_sessions",
+            ]
+        );
+    }
+
+    #[test]
+    fn baseline_databases_keep_their_users() {
+        let mut conn = baseline_database();
+        migrate(&mut conn).unwrap();
+
+        assert_eq!(schema_version(&conn).unwrap(), LATEST_VERSION);
+        let user = conn
+            .query_row(
+                "SELECT username, reminder_frequency, timezone, delivery_target, paused, presence_aware
+                 FROM users WHERE user_id = 42",
+                [],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, bool>(4)?,
+                        row.get::<_, bool>(5)?,
+                    ))
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            user,
+            (
+                "alice".to_string(),
+                "OneHour".to_string(),
+                "UTC".to_string(),
+                "DirectMessage".to_string(),
+                false,
+                false,
+            )
+        );
+    }
+
+    #[test]
+    fn baseline_and_new_databases_end_up_with_the_same_schema() {
+        let mut migrated = baseline_database();
+        migrate(&mut migrated).unwrap();
+        let mut created = Connection::open_in_memory().unwrap();
+        migrate(&mut created).unwrap();
+
+        assert_eq!(schema(&migrated), schema(&created));
+    }
+
+    #[test]
+    fn partially_upgraded_databases_are_completed() {
+        // Upgraded at startup by a release from before versioned migrations
+        let mut conn = baseline_database();
+        conn.execute_batch(
+            "ALTER TABLE users ADD COLUMN timezone TEXT NOT NULL DEFAULT 'UTC';
+             ALTER TABLE users ADD COLUMN active_start TEXT;
+             ALTER TABLE users ADD COLUMN active_end TEXT;
+             UPDATE users SET timezone = 'Europe/Paris', active_start = '09:00', active_end = '18:00';
+             CREATE TABLE delivery_attempts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id INTEGER NOT NULL,
+                attempted_at TEXT NOT NULL,
+                attempt INTEGER NOT NULL,
+                outcome TEXT NOT NULL,
+                error TEXT
+             );",
+        )
+        .unwrap();
+        migrate(&mut conn).unwrap();
+
+        let mut created = Connection::open_in_memory().unwrap();
+        migrate(&mut created).unwrap();
+        assert_eq!(schema(&conn), schema(&created));
+        let window = conn
+            .query_row(
+                "SELECT timezone, active_start, active_end FROM users WHERE user_id = 42",
+                [],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            window,
+            (
+                "Europe/Paris".to_string(),
+                "09:00".to_string(),
+                "18:00".to_string()
+            )
+        );
+    }
+
+    fn stored_times(conn: &Connection, table: &str, column: &str) -> Vec<Option<String>> {
+        conn.prepare(&format!("SELECT {column} FROM {table} ORDER BY rowid"))
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<SqliteResult<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn baseline_timestamps_are_converted() {
+        let mut conn = baseline_database();
+        migrate(&mut conn).unwrap();
+
+        assert_eq!(
+            stored_times(&conn, "users", "last_reminded"),
+            vec![Some("2025-06-01T09:00:00.000000000Z".to_string())]
+        );
+        assert_eq!(
+            stored_times(&conn, "users", "created_at"),
+            vec![Some("2025-05-01T08:30:00.123456000Z".to_string())]
+        );
+    }
+
+    #[test]
+    fn every_legacy_timestamp_is_converted() {
+        let mut conn = baseline_database();
+        apply_migrations(&mut conn, &MIGRATIONS[..2]).unwrap();
+        conn.execute_batch(
+            "UPDATE users SET snoozed_until = '2025-06-01 10:15:00.5', paused = 1,
+                paused_until = '2025-06-02 00:00:00';
+             INSERT INTO intake_events (user_id, drank_at, amount_ml)
+                VALUES (42, '2025-06-01 09:05:12.123456789', 250);
+             INSERT INTO adaptive_adjustments (user_id, adjusted_at, previous_minutes, new_minutes, reason)
+                VALUES (42, '2025-06-01 09:05:12', 60, 75, 'QuickAcknowledgement');
+             INSERT INTO delivery_attempts (user_id, attempted_at, attempt, outcome)
+                VALUES (42, '2025-06-01 09:00:00.000001', 1, 'Delivered');
+             INSERT INTO one_off_reminders (user_id, due_at) VALUES (42, '2025-06-03 18:30:00');
+             INSERT INTO voice_sessions (user_id, started_at, last_nudged_at, left_at)
+                VALUES (42, '2025-06-01 20:00:00.25', '2025-06-01 21:00:00.25', NULL);
+             INSERT INTO one_off_reminders (user_id, due_at) VALUES (42, 'someday');",
+        )
+        .unwrap();
+        migrate(&mut conn).unwrap();
+
+        let expected = [
+            (
+                "users",
+                "snoozed_until",
+                Some("2025-06-01T10:15:00.500000000Z"),
+            ),
+            (
+                "users",
+                "paused_until",
+                Some("2025-06-02T00:00:00.000000000Z"),
+            ),
+            (
+                "intake_events",
+                "drank_at",
+                Some("2025-06-01T09:05:12.123456789Z"),
+            ),
+            (
+                "adaptive_adjustments",
+                "adjusted_at",
+                Some("2025-06-01T09:05:12.000000000Z"),
+            ),
+            (
+                "delivery_attempts",
+                "attempted_at",
+                Some("2025-06-01T09:00:00.000001000Z"),
+            ),
+            (
+                "voice_sessions",
+                "started_at",
+                Some("2025-06-01T20:00:00.250000000Z"),
+            ),
+            (
+                "voice_sessions",
+                "last_nudged_at",
+                Some("2025-06-01T21:00:00.250000000Z"),
+            ),
+            ("voice_sessions", "left_at", None),
+        ];
+        for (table, column, time) in expected {
+            assert_eq!(
+                stored_times(&conn, table, column),
+                vec![time.map(str::to_string)],
+                "{table}.{column}"
+            );
+        }
+        // Values that can't be read are kept for a human to look at
+        assert_eq!(
+            stored_times(&conn, "one_off_reminders", "due_at"),
+            vec![
+                Some("2025-06-03T18:30:00.000000000Z".to_string()),
+                Some("someday".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn migrated_databases_are_left_untouched() {
+        let mut conn = baseline_database();
+        migrate(&mut conn).unwrap();
+        let before = schema(&conn);
+
+        migrate(&mut conn).unwrap();
+        assert_eq!(schema(&conn), before);
+        assert_eq!(schema_version(&conn).unwrap(), LATEST_VERSION);
+    }
+
+    #[test]
+    fn newer_databases_are_refused() {
+        let mut conn = baseline_database();
+        conn.pragma_update(None, "user_version", LATEST_VERSION + 1)
+            .unwrap();
+
+        let res = migrate(&mut conn);
+        assert!(matches!(
+            res,
+            Err(MigrationError::NewerSchema { found, supported })
+                if found == LATEST_VERSION + 1 && supported == LATEST_VERSION
+        ));
+        assert_eq!(table_columns(&conn, "users").unwrap().len(), 5);
+    }
+
+    #[test]
+    fn failed_migrations_are_rolled_back() {
+        let migrations = [
+            Migration {
+                description: "Create the users table",
+                apply: create_users_table,
+            },
+            Migration {
+                description: "Half applied",
+                apply: |conn| {
+                    conn.execute("ALTER TABLE users ADD COLUMN timezone TEXT", [])?;
+                    conn.execute("ALTER TABLE missing ADD COLUMN timezone TEXT", [])?;
+                    Ok(())
+                },
+            },
+        ];
+        let mut conn = baseline_database();
+
+        let res = apply_migrations(&mut conn, &migrations);
+        assert!(matches!(
+            res,
+            Err(MigrationError::Failed { version: 2, .. })
+        ));
+        assert_eq!(schema_version(&conn).unwrap(), 1);
+        assert_eq!(table_columns(&conn, "users").unwrap().len(), 5);
+    }
+}
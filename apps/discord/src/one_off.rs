@@ -0,0 +1,140 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use chrono::{NaiveDateTime, TimeDelta};
+use serenity::all::{CacheHttp, User, UserId};
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+
+use crate::{
+    database,
+    scheduler::{Job, SCHEDULER},
+};
+
+/// Pending one-off reminders a single user may have
+pub const MAX_PENDING_ONE_OFF_REMINDERS: usize = 10;
+
+/// Delay before a one-off reminder that couldn't be delivered for now is tried again
+pub fn one_off_retry_delay() -> TimeDelta {
+    TimeDelta::minutes(15)
+}
+
+/// A reminder sent once, whether the user is registered for recurring reminders or not
+#[derive(Debug, Clone)]
+pub struct OneOffReminder {
+    pub user: User,
+    pub due_at: NaiveDateTime,
+    pub note: Option<String>,
+}
+
+/// Pending one-off reminders, by database id
+pub static ONE_OFF_REMINDERS: LazyLock<RwLock<HashMap<i64, OneOffReminder>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+pub async fn lookup_pending_count(user: &User) -> usize {
+    ONE_OFF_REMINDERS
+        .read()
+        .await
+        .values()
+        .filter(|reminder| reminder.user.id == user.id)
+        .count()
+}
+
+/// Saves and schedules a one-off reminder, the database gives it its id
+pub async fn add_one_off_reminder(
+    user: &User,
+    due_at: NaiveDateTime,
+    note: Option<String>,
+) -> Result<(), rusqlite::Error> {
+    let id = database::insert_one_off_reminder(user, due_at, note.as_deref()).await?;
+
+    ONE_OFF_REMINDERS.write().await.insert(
+        id,
+        OneOffReminder {
+            user: user.clone(),
+            due_at,
+            note,
+        },
+    );
+    info!("Added one-off reminder {} for user {}", id, user.name);
+    SCHEDULER.schedule_job(Job::OneOff(id), user, Some(due_at));
+    Ok(())
+}
+
+pub async fn lookup_one_off_reminder(id: i64) -> Option<OneOffReminder> {
+    ONE_OFF_REMINDERS.read().await.get(&id).cloned()
+}
+
+/// Removes a one-off reminder once it has been delivered, or once it never can be. Until then it
+/// stays saved, so a reminder interrupted by a restart is sent after it.
+pub async fn remove_one_off_reminder(id: i64) {
+    if ONE_OFF_REMINDERS.write().await.remove(&id).is_none() {
+        return;
+    }
+
+    // Update database
+    if let Err(e) = database::remove_one_off_reminder(id).await {
+        error!(
+            "Failed to remove one-off reminder {} from database: {}",
+            id, e
+        );
+    }
+}
+
+/// Schedules a one-off reminder again after a transient delivery failure
+pub async fn retry_one_off_reminder(id: i64, now: NaiveDateTime) {
+    let Some(reminder) = lookup_one_off_reminder(id).await else {
+        return;
+    };
+    let retry_at = now + one_off_retry_delay();
+    info!(
+        "Retrying one-off reminder {} of user {} at {}",
+        id, reminder.user.name, retry_at
+    );
+    SCHEDULER.schedule_job(Job::OneOff(id), &reminder.user, Some(retry_at));
+}
+
+/// Overdue reminders are scheduled right away, they weren't sent while the bot was offline
+pub async fn load_one_off_reminders_from_database(cache_http: impl CacheHttp) {
+    let reminders = match database::load_one_off_reminders().await {
+        Ok(reminders) => reminders,
+        Err(e) => {
+            error!("Failed to load one-off reminders from database: {}", e);
+            return;
+        }
+    };
+
+    let mut users = HashMap::new();
+    let mut loaded = 0;
+    for stored in reminders.iter() {
+        let user_id = UserId::new(stored.user_id);
+        let user = match users.get(&user_id) {
+            Some(user) => user,
+            None => match user_id.to_user(&cache_http).await {
+                Ok(user) => users.entry(user_id).or_insert(user),
+                Err(e) => {
+                    warn!(
+                        "Cannot load one-off reminder {} of user {}: {}",
+                        stored.id, stored.user_id, e
+                    );
+                    continue;
+                }
+            },
+        };
+
+        ONE_OFF_REMINDERS.write().await.insert(
+            stored.id,
+            OneOffReminder {
+                user: user.clone(),
+                due_at: stored.due_at,
+                note: stored.note.clone(),
+            },
+        );
+        SCHEDULER.schedule_job(Job::OneOff(stored.id), user, Some(stored.due_at));
+        loaded += 1;
+    }
+    info!(
+        "Loaded {}/{} one-off reminders from database",
+        loaded,
+        reminders.len()
+    );
+}
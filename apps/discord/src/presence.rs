@@ -0,0 +1,319 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{LazyLock, Mutex},
+};
+
+use chrono::TimeDelta;
+use serenity::all::{OnlineStatus, Presence, User, UserId};
+use tokio::sync::mpsc::UnboundedReceiver;
+use tracing::info;
+
+pub static PRESENCES: LazyLock<Mutex<PresenceTracker>> =
+    LazyLock::new(|| Mutex::new(PresenceTracker::default()));
+
+/// Delay between a user coming back online and its deferred reminder, so it isn't the first
+/// thing waiting for them
+pub fn presence_return_delay() -> TimeDelta {
+    TimeDelta::minutes(2)
+}
+
+/// A change of status received from the gateway
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PresenceEvent {
+    /// Presences sent with a guild, where members missing are offline
+    Snapshot(Vec<(UserId, OnlineStatus)>),
+    Update(UserId, OnlineStatus),
+}
+
+impl PresenceEvent {
+    pub fn snapshot<'a>(presences: impl IntoIterator<Item = &'a Presence>) -> Self {
+        PresenceEvent::Snapshot(
+            presences
+                .into_iter()
+                .map(|presence| (presence.user.id, presence.status))
+                .collect(),
+        )
+    }
+}
+
+impl From<&Presence> for PresenceEvent {
+    fn from(presence: &Presence) -> Self {
+        PresenceEvent::Update(presence.user.id, presence.status)
+    }
+}
+
+/// Last known status of the guild members, and the users whose reminder waits for them to come
+/// back online
+#[derive(Debug, Default)]
+pub struct PresenceTracker {
+    statuses: HashMap<UserId, OnlineStatus>,
+    /// Until a first snapshot, unknown users are assumed available rather than offline
+    seeded: bool,
+    deferred: HashMap<UserId, User>,
+}
+
+/// Offline (or invisible) and Do Not Disturb users aren't reminded
+fn is_available_status(status: OnlineStatus) -> bool {
+    matches!(status, OnlineStatus::Online | OnlineStatus::Idle)
+}
+
+impl PresenceTracker {
+    pub fn is_available(&self, user_id: UserId) -> bool {
+        match self.statuses.get(&user_id) {
+            Some(status) => is_available_status(*status),
+            None => !self.seeded,
+        }
+    }
+
+    /// Keeps the reminder of an unavailable user until it comes back, returns false when the
+    /// user is available
+    pub fn defer_if_unavailable(&mut self, user: &User) -> bool {
+        if self.is_available(user.id) {
+            return false;
+        }
+        self.deferred.insert(user.id, user.clone());
+        true
+    }
+
+    pub fn cancel_deferred(&mut self, user_id: UserId) {
+        self.deferred.remove(&user_id);
+    }
+
+    /// Applies a change of status, returning the deferred users that just came back
+    pub fn apply(&mut self, event: PresenceEvent) -> Vec<User> {
+        let was_available: HashSet<UserId> = self
+            .deferred
+            .keys()
+            .copied()
+            .filter(|user_id| self.is_available(*user_id))
+            .collect();
+
+        match event {
+            PresenceEvent::Snapshot(presences) => {
+                self.seeded = true;
+                // Members missing from a snapshot are offline
+                self.statuses
+                    .values_mut()
+                    .for_each(|status| *status = OnlineStatus::Offline);
+                self.statuses.extend(presences);
+            }
+            PresenceEvent::Update(user_id, status) => {
+                self.statuses.insert(user_id, status);
+            }
+        }
+
+        let returned: Vec<UserId> = self
+            .deferred
+            .keys()
+            .copied()
+            .filter(|user_id| !was_available.contains(user_id) && self.is_available(*user_id))
+            .collect();
+        returned
+            .into_iter()
+            .filter_map(|user_id| self.deferred.remove(&user_id))
+            .collect()
+    }
+}
+
+/// Defers the reminder of a user who is offline or doesn't want to be disturbed, returns false
+/// when the reminder can be sent right away
+pub fn defer_if_unavailable(user: &User) -> bool {
+    PRESENCES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .defer_if_unavailable(user)
+}
+
+/// Forgets the reminder held back for a user, once something else decides when it comes
+pub fn cancel_deferred_reminder(user_id: UserId) {
+    PRESENCES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .cancel_deferred(user_id);
+}
+
+/// Applies every presence received from the gateway, calling `on_return` for each deferred user
+/// coming back online. Stops once every sender is dropped.
+pub async fn track_presences(
+    tracker: &Mutex<PresenceTracker>,
+    mut events: UnboundedReceiver<PresenceEvent>,
+    mut on_return: impl FnMut(User),
+) {
+    while let Some(event) = events.recv().await {
+        let returned = tracker
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .apply(event);
+        for user in returned {
+            info!("User {} is back online, sending its reminder", user.name);
+            on_return(user);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::sync::mpsc::unbounded_channel;
+
+    fn user(id: u64) -> User {
+        let mut user = User::default();
+        user.id = UserId::new(id);
+        user.name = format!("user{id}");
+        user
+    }
+
+    /// Feeds a mocked gateway stream to the tracker, returning the users that came back in order
+    async fn replay(tracker: &Mutex<PresenceTracker>, events: Vec<PresenceEvent>) -> Vec<UserId> {
+        let (sender, receiver) = unbounded_channel();
+        for event in events {
+            sender.send(event).unwrap();
+        }
+        drop(sender);
+
+        let mut returned = Vec::new();
+        track_presences(tracker, receiver, |user| returned.push(user.id)).await;
+        returned
+    }
+
+    fn defer(tracker: &Mutex<PresenceTracker>, user: &User) -> bool {
+        tracker.lock().unwrap().defer_if_unavailable(user)
+    }
+
+    #[tokio::test]
+    async fn unknown_users_are_available_until_a_snapshot() {
+        let tracker = Mutex::new(PresenceTracker::default());
+        assert!(tracker.lock().unwrap().is_available(UserId::new(1)));
+
+        let returned = replay(
+            &tracker,
+            vec![PresenceEvent::Snapshot(vec![(
+                UserId::new(2),
+                OnlineStatus::Online,
+            )])],
+        )
+        .await;
+
+        assert!(returned.is_empty());
+        let tracker = tracker.lock().unwrap();
+        assert!(!tracker.is_available(UserId::new(1)));
+        assert!(tracker.is_available(UserId::new(2)));
+    }
+
+    #[tokio::test]
+    async fn deferred_reminder_is_released_when_the_user_comes_online() {
+        let tracker = Mutex::new(PresenceTracker::default());
+        let alice = user(1);
+        replay(
+            &tracker,
+            vec![PresenceEvent::Update(alice.id, OnlineStatus::Offline)],
+        )
+        .await;
+        assert!(defer(&tracker, &alice));
+
+        let returned = replay(
+            &tracker,
+            vec![
+                PresenceEvent::Update(alice.id, OnlineStatus::Invisible),
+                PresenceEvent::Update(alice.id, OnlineStatus::Online),
+                PresenceEvent::Update(alice.id, OnlineStatus::Idle),
+            ],
+        )
+        .await;
+
+        assert_eq!(returned, vec![alice.id]);
+    }
+
+    #[tokio::test]
+    async fn do_not_disturb_defers_reminders() {
+        let tracker = Mutex::new(PresenceTracker::default());
+        let alice = user(1);
+        replay(
+            &tracker,
+            vec![PresenceEvent::Update(alice.id, OnlineStatus::DoNotDisturb)],
+        )
+        .await;
+        assert!(defer(&tracker, &alice));
+
+        let returned = replay(
+            &tracker,
+            vec![
+                PresenceEvent::Update(alice.id, OnlineStatus::Offline),
+                PresenceEvent::Update(alice.id, OnlineStatus::DoNotDisturb),
+                PresenceEvent::Update(alice.id, OnlineStatus::Idle),
+            ],
+        )
+        .await;
+
+        assert_eq!(returned, vec![alice.id]);
+    }
+
+    #[tokio::test]
+    async fn online_users_are_not_deferred() {
+        let tracker = Mutex::new(PresenceTracker::default());
+        let alice = user(1);
+        replay(
+            &tracker,
+            vec![PresenceEvent::Snapshot(vec![(
+                alice.id,
+                OnlineStatus::Online,
+            )])],
+        )
+        .await;
+
+        assert!(!defer(&tracker, &alice));
+        let returned = replay(
+            &tracker,
+            vec![
+                PresenceEvent::Update(alice.id, OnlineStatus::Offline),
+                PresenceEvent::Update(alice.id, OnlineStatus::Online),
+            ],
+        )
+        .await;
+        assert!(returned.is_empty());
+    }
+
+    #[tokio::test]
+    async fn snapshot_releases_members_back_online_only() {
+        let tracker = Mutex::new(PresenceTracker::default());
+        let (alice, bob) = (user(1), user(2));
+        replay(&tracker, vec![PresenceEvent::Snapshot(Vec::new())]).await;
+        assert!(defer(&tracker, &alice));
+        assert!(defer(&tracker, &bob));
+
+        // Bob is missing from the snapshot sent on reconnection, so still offline
+        let returned = replay(
+            &tracker,
+            vec![PresenceEvent::Snapshot(vec![(
+                alice.id,
+                OnlineStatus::Online,
+            )])],
+        )
+        .await;
+        assert_eq!(returned, vec![alice.id]);
+
+        let returned = replay(
+            &tracker,
+            vec![PresenceEvent::Update(bob.id, OnlineStatus::Online)],
+        )
+        .await;
+        assert_eq!(returned, vec![bob.id]);
+    }
+
+    #[tokio::test]
+    async fn cancelled_deferrals_are_not_released() {
+        let tracker = Mutex::new(PresenceTracker::default());
+        let alice = user(1);
+        replay(&tracker, vec![PresenceEvent::Snapshot(Vec::new())]).await;
+        assert!(defer(&tracker, &alice));
+        tracker.lock().unwrap().cancel_deferred(alice.id);
+
+        let returned = replay(
+            &tracker,
+            vec![PresenceEvent::Update(alice.id, OnlineStatus::Online)],
+        )
+        .await;
+        assert!(returned.is_empty());
+    }
+}
@@ -1,51 +1,824 @@
-use std::{collections::HashMap, sync::LazyLock};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::LazyLock,
+};
 
-use chrono::{NaiveDateTime, TimeDelta};
-use serenity::all::{CacheHttp, User};
+use chrono::{DateTime, NaiveDateTime, TimeDelta, TimeZone, Weekday};
+use chrono_tz::Tz;
+use serenity::all::{CacheHttp, ChannelId, User, UserId};
 use tokio::sync::RwLock;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
-use crate::database;
+use crate::{
+    adaptive::{AdaptiveBounds, AdjustmentReason, adjusted_minutes, quick_acknowledgement_delay},
+    database::{self, StoredUser},
+    delivery::DeliveryTarget,
+    dispatch::DeliveryOutcome,
+    goal::{DailyGoal, GoalDay, drank_today, rebalanced_frequency},
+    presence::cancel_deferred_reminder,
+    schedule::{
+        Absence, ActiveWindow, CronSchedule, DayProfile, WeekProfiles, next_active_time,
+        next_reminder_time,
+    },
+    scheduler::{Job, SCHEDULER},
+    voice::end_voice_session,
+};
 
-#[derive(Debug, Clone, Copy)]
+pub const MIN_CUSTOM_FREQUENCY_MINUTES: u32 = 10;
+pub const MAX_CUSTOM_FREQUENCY_MINUTES: u32 = 8 * 60;
+
+/// Delay before a welcome back that couldn't be delivered for now is tried again
+fn welcome_back_retry_delay() -> TimeDelta {
+    TimeDelta::minutes(15)
+}
+
+/// Consecutive permanent delivery failures after which a registration gets suspended
+pub const MAX_CONSECUTIVE_DELIVERY_FAILURES: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ReminderFrequency {
-    ThirtyMin,  // 30 min
-    OneHour,    // 1h
-    ThreeHours, // 3h
+    ThirtyMin,   // 30 min
+    OneHour,     // 1h
+    ThreeHours,  // 3h
+    Custom(u32), // any amount of minutes, between 10 min and 8h
+}
+
+impl ReminderFrequency {
+    /// Builds a custom frequency, rejecting intervals outside of the allowed bounds
+    pub fn custom(minutes: u32) -> Option<Self> {
+        (MIN_CUSTOM_FREQUENCY_MINUTES..=MAX_CUSTOM_FREQUENCY_MINUTES)
+            .contains(&minutes)
+            .then_some(ReminderFrequency::Custom(minutes))
+    }
+
+    pub fn minutes(&self) -> u32 {
+        match self {
+            ReminderFrequency::ThirtyMin => 30,
+            ReminderFrequency::OneHour => 60,
+            ReminderFrequency::ThreeHours => 3 * 60,
+            ReminderFrequency::Custom(minutes) => *minutes,
+        }
+    }
 }
 
 impl From<ReminderFrequency> for TimeDelta {
     fn from(value: ReminderFrequency) -> Self {
-        match value {
-            ReminderFrequency::ThirtyMin => TimeDelta::minutes(30),
-            ReminderFrequency::OneHour => TimeDelta::hours(1),
-            ReminderFrequency::ThreeHours => TimeDelta::hours(3),
+        TimeDelta::minutes(value.minutes() as i64)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UserSettings {
+    pub frequency: ReminderFrequency,
+    pub timezone: Tz,
+    pub active_window: Option<ActiveWindow>,
+    pub profiles: WeekProfiles,
+    /// When set, the default frequency adapts itself to the user's acknowledgements
+    pub adaptive: Option<AdaptiveBounds>,
+    pub delivery: DeliveryTarget,
+    /// Used when the reminder can't be delivered to `delivery`
+    pub fallback: Option<DeliveryTarget>,
+    /// When set, replaces the frequency, the active window and the day profiles
+    pub cron: Option<CronSchedule>,
+    /// When set, the default frequency spreads this goal over the active window
+    pub goal: Option<DailyGoal>,
+    /// Reminders wait for the user to be online and not in Do Not Disturb
+    pub presence_aware: bool,
+    /// When set, a nudge is sent after this many minutes spent in voice channels
+    pub voice_nudge_minutes: Option<u32>,
+}
+
+impl UserSettings {
+    pub fn new(frequency: ReminderFrequency, timezone: Tz) -> Self {
+        Self {
+            frequency,
+            timezone,
+            active_window: None,
+            profiles: WeekProfiles::default(),
+            adaptive: None,
+            delivery: DeliveryTarget::DirectMessage,
+            fallback: None,
+            cron: None,
+            goal: None,
+            presence_aware: false,
+            voice_nudge_minutes: None,
         }
     }
+
+    /// Converts a stored UTC time to the user's local time, daylight-saving included
+    pub fn local_time(&self, utc: NaiveDateTime) -> DateTime<Tz> {
+        self.timezone.from_utc_datetime(&utc)
+    }
 }
 
-pub static REGISTRED_USERS: LazyLock<RwLock<HashMap<User, ReminderFrequency>>> =
+pub static REGISTRED_USERS: LazyLock<RwLock<HashMap<User, UserSettings>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Registered users by id, for the gateway events which only carry the id of a user
+static REGISTERED_IDS: LazyLock<RwLock<HashMap<UserId, User>>> =
     LazyLock::new(|| RwLock::new(HashMap::new()));
 
 pub static LAST_REMINDED_TIME: LazyLock<RwLock<HashMap<User, NaiveDateTime>>> =
     LazyLock::new(|| RwLock::new(HashMap::new()));
 
-pub async fn insert_new_user_to_remind(user: &User, frequency: ReminderFrequency) {
-    let now = chrono::Utc::now().naive_utc();
+/// Next reminder time chosen by the user through the reminder buttons, replacing the scheduled one
+pub static SNOOZED_UNTIL: LazyLock<RwLock<HashMap<User, NaiveDateTime>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Users who haven't reacted to their last reminder yet
+pub static AWAITING_ACKNOWLEDGEMENT: LazyLock<RwLock<HashSet<User>>> =
+    LazyLock::new(|| RwLock::new(HashSet::new()));
+
+/// Consecutive permanent delivery failures of users whose last reminders couldn't be delivered
+pub static DELIVERY_FAILURES: LazyLock<RwLock<HashMap<User, u32>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Users who can't receive DMs anymore, they aren't reminded until they register again
+pub static SUSPENDED_USERS: LazyLock<RwLock<HashSet<User>>> =
+    LazyLock::new(|| RwLock::new(HashSet::new()));
+
+/// Reminders of a paused user are suppressed until the pause ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseState {
+    /// Until the user resumes its reminders
+    Indefinite,
+    Until(NaiveDateTime),
+}
 
+pub static PAUSED_USERS: LazyLock<RwLock<HashMap<User, PauseState>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Upcoming and ongoing absences of users, earliest first
+pub static ABSENCES: LazyLock<RwLock<HashMap<User, Vec<Absence>>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Private threads hosting the reminders of users delivered in a thread
+pub static REMINDER_THREADS: LazyLock<RwLock<HashMap<User, ChannelId>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+pub async fn insert_new_user_to_remind(user: &User, settings: UserSettings, now: NaiveDateTime) {
     REGISTRED_USERS
         .write()
         .await
-        .insert(user.clone(), frequency);
+        .insert(user.clone(), settings.clone());
+    REGISTERED_IDS.write().await.insert(user.id, user.clone());
     info!("Inserted {} in registred users", user.name);
 
     LAST_REMINDED_TIME.write().await.insert(user.clone(), now);
     info!("Inserted last updated time for user {} as now", user.name);
 
+    SNOOZED_UNTIL.write().await.remove(user);
+    AWAITING_ACKNOWLEDGEMENT.write().await.remove(user);
+    DELIVERY_FAILURES.write().await.remove(user);
+    PAUSED_USERS.write().await.remove(user);
+    // Registering again is how a suspended user resumes its reminders
+    if SUSPENDED_USERS.write().await.remove(user) {
+        info!("Lifted the suspension of user {}", user.name);
+    }
+    reschedule_user(user).await;
+
     // Save to database
-    if let Err(e) = database::save_user_reminder(user, frequency, now).await {
+    if let Err(e) = database::save_user_reminder(user, &settings, now, now).await {
         error!("Failed to save user {} to database: {}", user.name, e);
     }
+    // The reminder thread of a previous registration is reused
+    if let Some(thread_id) = lookup_reminder_thread(user).await
+        && let Err(e) = database::update_user_reminder_thread(user, Some(thread_id.get())).await
+    {
+        error!("Failed to update user {} in database: {}", user.name, e);
+    }
+}
+
+pub async fn lookup_last_reminded(user: &User) -> Option<NaiveDateTime> {
+    LAST_REMINDED_TIME.read().await.get(user).copied()
+}
+
+/// When the next reminder of the user is due, a snooze taking precedence over its schedule, and
+/// pauses and absences over both
+pub async fn next_due_time(user: &User) -> Option<NaiveDateTime> {
+    if is_user_suspended(user).await {
+        return None;
+    }
+    let snoozed_until = SNOOZED_UNTIL.read().await.get(user).copied();
+    let settings = lookup_user_settings(user).await?;
+    let due = match snoozed_until {
+        Some(until) => next_active_time(&settings, until)?,
+        None => next_reminder_time(&settings, lookup_last_reminded(user).await?)?,
+    };
+
+    let mut due = match lookup_user_pause(user).await {
+        Some(PauseState::Indefinite) => return None,
+        Some(PauseState::Until(until)) if due < until => next_active_time(&settings, until)?,
+        _ => due,
+    };
+
+    // Absences are sorted, so back-to-back ones are skipped one after the other
+    for absence in lookup_user_absences(user).await {
+        if absence.contains(settings.timezone, due) {
+            due = next_active_time(&settings, absence.ends_at(settings.timezone))?;
+        }
+    }
+    Some(due)
+}
+
+/// Must be called after any change affecting the next reminder of the user
+pub async fn reschedule_user(user: &User) {
+    SCHEDULER.schedule(user, next_due_time(user).await);
+}
+
+/// Schedules the reminder held back while the user was offline or busy, once it is back. It comes
+/// `not_before` at the earliest, and still follows the active window, pauses and absences.
+pub async fn release_deferred_reminder(user: &User, not_before: NaiveDateTime) {
+    let Some(due) = next_due_time(user).await else {
+        return;
+    };
+    let Some(settings) = lookup_user_settings(user).await else {
+        return;
+    };
+    SCHEDULER.schedule(user, next_active_time(&settings, due.max(not_before)));
+}
+
+pub async fn snooze_user_reminder(user: &User, until: NaiveDateTime) {
+    SNOOZED_UNTIL.write().await.insert(user.clone(), until);
+    info!("Snoozed reminders of user {} until {}", user.name, until);
+    reschedule_user(user).await;
+
+    // Update database
+    if let Err(e) = database::update_user_snoozed_until(user, Some(until)).await {
+        error!("Failed to update user {} in database: {}", user.name, e);
+    }
+}
+
+pub async fn record_user_intake(user: &User, drank_at: NaiveDateTime, amount_ml: Option<u32>) {
+    if let Err(e) = database::insert_intake_event(user, drank_at, amount_ml).await {
+        error!(
+            "Failed to save intake event of user {} to database: {}",
+            user.name, e
+        );
+    }
+    rebalance_user_goal(user, drank_at).await;
+}
+
+/// Paces the rest of the day of a user with a daily goal from what it drank so far, after each
+/// intake and each reminder
+pub async fn rebalance_user_goal(user: &User, now: NaiveDateTime) {
+    let Some(settings) = lookup_user_settings(user).await else {
+        return;
+    };
+    let (Some(goal), Some(window)) = (settings.goal, settings.active_window) else {
+        return;
+    };
+    let pace = goal.pace(window);
+    let day = GoalDay::new(window, settings.timezone, now);
+    let drank_ml = drank_today(user, pace, day).await;
+    let frequency = rebalanced_frequency(goal, pace, day, drank_ml);
+
+    {
+        let mut registered_users = REGISTRED_USERS.write().await;
+        let Some(settings) = registered_users.get_mut(user) else {
+            return;
+        };
+        if settings.goal != Some(goal) || settings.frequency == frequency {
+            return;
+        }
+        settings.frequency = frequency;
+    }
+    info!(
+        "Rebalanced daily goal of user {} to every {} minutes, {} of {} ml drank",
+        user.name,
+        frequency.minutes(),
+        drank_ml,
+        goal.target_ml
+    );
+    reschedule_user(user).await;
+
+    // Update database
+    if let Err(e) = database::update_user_frequency(user, frequency, None).await {
+        error!("Failed to update user {} in database: {}", user.name, e);
+    }
+}
+
+/// Amounts of the intake events since the given time, None when no amount was chosen
+pub async fn lookup_intake_since(user: &User, since: NaiveDateTime) -> Vec<Option<u32>> {
+    database::load_intake_amounts_since(user, since)
+        .await
+        .unwrap_or_else(|e| {
+            error!(
+                "Failed to load intake events of user {} from database: {}",
+                user.name, e
+            );
+            Vec::new()
+        })
+}
+
+pub async fn record_delivery_attempt(
+    user: &User,
+    attempted_at: NaiveDateTime,
+    attempt: u32,
+    target: DeliveryTarget,
+    outcome: DeliveryOutcome,
+    error: Option<String>,
+) {
+    if let Err(e) =
+        database::insert_delivery_attempt(user, attempted_at, attempt, target, outcome, error).await
+    {
+        error!(
+            "Failed to save delivery attempt of user {} to database: {}",
+            user.name, e
+        );
+    }
+}
+
+pub async fn lookup_user_pause(user: &User) -> Option<PauseState> {
+    PAUSED_USERS.read().await.get(user).copied()
+}
+
+/// Returns false when the user isn't registered
+pub async fn pause_user_reminders(user: &User, pause: PauseState) -> bool {
+    if !REGISTRED_USERS.read().await.contains_key(user) {
+        return false;
+    }
+
+    PAUSED_USERS.write().await.insert(user.clone(), pause);
+    info!("Paused reminders of user {} : {:?}", user.name, pause);
+    cancel_deferred_reminder(user.id);
+    reschedule_user(user).await;
+
+    // Update database
+    if let Err(e) = database::update_user_pause(user, Some(pause)).await {
+        error!("Failed to update user {} in database: {}", user.name, e);
+    }
+    true
+}
+
+/// Returns false when the user wasn't paused
+pub async fn resume_user_reminders(user: &User) -> bool {
+    if PAUSED_USERS.write().await.remove(user).is_none() {
+        return false;
+    }
+
+    info!("Resumed reminders of user {}", user.name);
+    reschedule_user(user).await;
+
+    // Update database
+    if let Err(e) = database::update_user_pause(user, None).await {
+        error!("Failed to update user {} in database: {}", user.name, e);
+    }
+    true
+}
+
+pub async fn lookup_user_absences(user: &User) -> Vec<Absence> {
+    ABSENCES.read().await.get(user).cloned().unwrap_or_default()
+}
+
+/// Returns false when the user isn't registered
+pub async fn add_user_absence(user: &User, absence: Absence) -> bool {
+    if !REGISTRED_USERS.read().await.contains_key(user) {
+        return false;
+    }
+
+    {
+        let mut absences = ABSENCES.write().await;
+        let user_absences = absences.entry(user.clone()).or_default();
+        user_absences.push(absence);
+        user_absences.sort_by_key(|absence| absence.start);
+    }
+    info!("Added absence {} for user {}", absence, user.name);
+    cancel_deferred_reminder(user.id);
+    reschedule_user(user).await;
+    reschedule_welcome_back(user).await;
+
+    // Save to database
+    if let Err(e) = database::insert_absence(user, absence).await {
+        error!(
+            "Failed to save absence of user {} to database: {}",
+            user.name, e
+        );
+    }
+    true
+}
+
+/// Cancels every upcoming and ongoing absence of the user, returning how many there were
+pub async fn clear_user_absences(user: &User) -> usize {
+    let Some(absences) = ABSENCES.write().await.remove(user) else {
+        return 0;
+    };
+    info!("Cancelled the absences of user {}", user.name);
+    reschedule_user(user).await;
+    reschedule_welcome_back(user).await;
+
+    // Update database
+    if let Err(e) = database::remove_absences(user, &absences).await {
+        error!(
+            "Failed to remove absences of user {} from database: {}",
+            user.name, e
+        );
+    }
+    absences.len()
+}
+
+/// Schedules the welcome back of the user for the end of its earliest absence, at the next active
+/// time
+pub async fn reschedule_welcome_back(user: &User) {
+    let due = match lookup_user_settings(user).await {
+        Some(settings) => lookup_user_absences(user)
+            .await
+            .iter()
+            .map(|absence| absence.ends_at(settings.timezone))
+            .min()
+            .map(|ends_at| next_active_time(&settings, ends_at).unwrap_or(ends_at)),
+        None => None,
+    };
+    SCHEDULER.schedule_job(Job::WelcomeBack(user.id), user, due);
+}
+
+/// Schedules the welcome back again after a transient delivery failure, its absence is kept
+/// meanwhile
+pub fn retry_welcome_back(user: &User, now: NaiveDateTime) {
+    let retry_at = now + welcome_back_retry_delay();
+    info!(
+        "Retrying the welcome back of user {} at {}",
+        user.name, retry_at
+    );
+    SCHEDULER.schedule_job(Job::WelcomeBack(user.id), user, Some(retry_at));
+}
+
+/// Forgets the absences of the user that are over at `now`, once the user got welcomed back,
+/// returning whether there were any
+pub async fn take_ended_absences(user: &User, now: NaiveDateTime) -> bool {
+    let Some(settings) = lookup_user_settings(user).await else {
+        return false;
+    };
+
+    let ended = {
+        let mut absences = ABSENCES.write().await;
+        let Some(user_absences) = absences.get_mut(user) else {
+            return false;
+        };
+        let (ended, upcoming): (Vec<_>, Vec<_>) = user_absences
+            .iter()
+            .partition(|absence| absence.ends_at(settings.timezone) <= now);
+        *user_absences = upcoming;
+        if user_absences.is_empty() {
+            absences.remove(user);
+        }
+        ended
+    };
+    reschedule_welcome_back(user).await;
+    if ended.is_empty() {
+        return false;
+    }
+
+    // Update database
+    if let Err(e) = database::remove_absences(user, &ended).await {
+        error!(
+            "Failed to remove absences of user {} from database: {}",
+            user.name, e
+        );
+    }
+    true
+}
+
+pub async fn lookup_reminder_thread(user: &User) -> Option<ChannelId> {
+    REMINDER_THREADS.read().await.get(user).copied()
+}
+
+/// Remembers the private thread hosting the reminders of the user, None forgets a deleted one
+pub async fn update_user_reminder_thread(user: &User, thread_id: Option<ChannelId>) {
+    match thread_id {
+        Some(thread_id) => REMINDER_THREADS
+            .write()
+            .await
+            .insert(user.clone(), thread_id),
+        None => REMINDER_THREADS.write().await.remove(user),
+    };
+
+    // Update database
+    if let Err(e) =
+        database::update_user_reminder_thread(user, thread_id.map(|thread_id| thread_id.get()))
+            .await
+    {
+        error!("Failed to update user {} in database: {}", user.name, e);
+    }
+}
+
+pub async fn is_user_suspended(user: &User) -> bool {
+    SUSPENDED_USERS.read().await.contains(user)
+}
+
+/// Whether the user's reminders are suspended, paused or in an absence right now
+pub async fn is_user_on_break(user: &User, now: NaiveDateTime) -> bool {
+    if is_user_suspended(user).await {
+        return true;
+    }
+    match lookup_user_pause(user).await {
+        Some(PauseState::Indefinite) => return true,
+        Some(PauseState::Until(until)) if now < until => return true,
+        _ => {}
+    }
+    let Some(settings) = lookup_user_settings(user).await else {
+        return false;
+    };
+    lookup_user_absences(user)
+        .await
+        .iter()
+        .any(|absence| absence.contains(settings.timezone, now))
+}
+
+/// Called once a reminder got delivered, the failure streak of the user is over
+pub async fn record_delivery_success(user: &User) {
+    if DELIVERY_FAILURES.write().await.remove(user).is_none() {
+        return;
+    }
+
+    // Update database
+    if let Err(e) = database::update_user_delivery_failures(user, 0, false).await {
+        error!("Failed to update user {} in database: {}", user.name, e);
+    }
+}
+
+/// Counts a permanent delivery failure, and suspends the user once there are too many in a row.
+/// Returns whether the user just got suspended.
+pub async fn record_permanent_delivery_failure(user: &User) -> bool {
+    let failures = {
+        let mut delivery_failures = DELIVERY_FAILURES.write().await;
+        let failures = delivery_failures.entry(user.clone()).or_insert(0);
+        *failures += 1;
+        *failures
+    };
+
+    let suspended = failures >= MAX_CONSECUTIVE_DELIVERY_FAILURES
+        && SUSPENDED_USERS.write().await.insert(user.clone());
+    if suspended {
+        warn!(
+            "Suspended reminders of user {} after {} undelivered reminders",
+            user.name, failures
+        );
+        cancel_deferred_reminder(user.id);
+        reschedule_user(user).await;
+    }
+
+    // Update database
+    if let Err(e) =
+        database::update_user_delivery_failures(user, failures, is_user_suspended(user).await).await
+    {
+        error!("Failed to update user {} in database: {}", user.name, e);
+    }
+
+    suspended
+}
+
+/// Moves the next reminder one interval after `now` without counting a reminder as sent, used
+/// when a reminder couldn't be delivered
+pub async fn postpone_user_reminder(user: &User, now: NaiveDateTime) {
+    let Some(settings) = lookup_user_settings(user).await else {
+        return;
+    };
+    match next_reminder_time(&settings, now) {
+        Some(until) => snooze_user_reminder(user, until).await,
+        None => reschedule_user(user).await,
+    }
+}
+
+/// Called whenever the user reacts to a reminder, drinking quickly makes an adaptive interval grow
+pub async fn acknowledge_user_reminder(user: &User, now: NaiveDateTime, drank: bool) {
+    if !AWAITING_ACKNOWLEDGEMENT.write().await.remove(user) {
+        return;
+    }
+
+    let quick = lookup_last_reminded(user)
+        .await
+        .is_some_and(|last_reminded| now - last_reminded <= quick_acknowledgement_delay());
+    if drank && quick {
+        adjust_adaptive_frequency(user, AdjustmentReason::QuickAcknowledgement, now).await;
+    }
+
+    // Update database
+    if let Err(e) = database::update_user_awaiting_acknowledgement(user, false).await {
+        error!("Failed to update user {} in database: {}", user.name, e);
+    }
+}
+
+async fn adjust_adaptive_frequency(user: &User, reason: AdjustmentReason, now: NaiveDateTime) {
+    let (previous, frequency, bounds) = {
+        let mut registered_users = REGISTRED_USERS.write().await;
+        let Some(settings) = registered_users.get_mut(user) else {
+            return;
+        };
+        let Some(bounds) = settings.adaptive else {
+            return;
+        };
+
+        let previous = settings.frequency.minutes();
+        let new = adjusted_minutes(previous, bounds, reason);
+        if new == previous {
+            return;
+        }
+        settings.frequency = ReminderFrequency::Custom(new);
+        (previous, settings.frequency, bounds)
+    };
+    info!(
+        "Adjusted adaptive frequency of user {} from {} to {} minutes : {:?}",
+        user.name,
+        previous,
+        frequency.minutes(),
+        reason
+    );
+    reschedule_user(user).await;
+
+    // Update database
+    if let Err(e) = database::update_user_frequency(user, frequency, Some(bounds)).await {
+        error!("Failed to update user {} in database: {}", user.name, e);
+    }
+    if let Err(e) =
+        database::insert_adaptive_adjustment(user, now, previous, frequency.minutes(), reason).await
+    {
+        error!(
+            "Failed to save adaptive adjustment of user {} to database: {}",
+            user.name, e
+        );
+    }
+}
+
+/// Enables (with bounds) or disables the adaptive mode, returns the new frequency or None when the user isn't registered
+pub async fn update_user_adaptive(
+    user: &User,
+    adaptive: Option<AdaptiveBounds>,
+) -> Option<ReminderFrequency> {
+    let frequency = {
+        let mut registered_users = REGISTRED_USERS.write().await;
+        let settings = registered_users.get_mut(user)?;
+        settings.adaptive = adaptive;
+        if let Some(bounds) = adaptive {
+            settings.frequency =
+                ReminderFrequency::Custom(bounds.clamp(settings.frequency.minutes()));
+        }
+        settings.frequency
+    };
+    info!(
+        "Updated adaptive mode for user {} as {:?}, frequency is {:?}",
+        user.name, adaptive, frequency
+    );
+    reschedule_user(user).await;
+
+    // Update database
+    if let Err(e) = database::update_user_frequency(user, frequency, adaptive).await {
+        error!("Failed to update user {} in database: {}", user.name, e);
+    }
+    Some(frequency)
+}
+
+pub async fn lookup_user_settings(user: &User) -> Option<UserSettings> {
+    REGISTRED_USERS.read().await.get(user).cloned()
+}
+
+/// Returns false when the user isn't registered
+pub async fn update_user_timezone(user: &User, timezone: Tz) -> bool {
+    match REGISTRED_USERS.write().await.get_mut(user) {
+        Some(settings) => settings.timezone = timezone,
+        None => return false,
+    }
+    info!("Updated timezone for user {} as {}", user.name, timezone);
+    reschedule_user(user).await;
+    // Absences end at midnight in the user's time zone
+    reschedule_welcome_back(user).await;
+
+    // Update database
+    if let Err(e) = database::update_user_timezone(user, timezone).await {
+        error!("Failed to update user {} in database: {}", user.name, e);
+    }
+    true
+}
+
+/// Returns false when the user isn't registered
+pub async fn update_user_active_window(user: &User, active_window: Option<ActiveWindow>) -> bool {
+    // A daily goal is paced again over the new window
+    let paced = match REGISTRED_USERS.write().await.get_mut(user) {
+        Some(settings) => {
+            settings.active_window = active_window;
+            settings.goal.zip(active_window).map(|(goal, window)| {
+                settings.frequency = goal.pace(window).frequency;
+                settings.frequency
+            })
+        }
+        None => return false,
+    };
+    info!(
+        "Updated active window for user {} as {:?}",
+        user.name, active_window
+    );
+    reschedule_user(user).await;
+
+    // Update database
+    if let Err(e) = database::update_user_active_window(user, active_window).await {
+        error!("Failed to update user {} in database: {}", user.name, e);
+    }
+    if let Some(frequency) = paced
+        && let Err(e) = database::update_user_frequency(user, frequency, None).await
+    {
+        error!("Failed to update user {} in database: {}", user.name, e);
+    }
+    true
+}
+
+/// Returns false when the user isn't registered
+pub async fn update_user_presence_aware(user: &User, presence_aware: bool) -> bool {
+    match REGISTRED_USERS.write().await.get_mut(user) {
+        Some(settings) => settings.presence_aware = presence_aware,
+        None => return false,
+    }
+    info!(
+        "Updated presence awareness for user {} as {}",
+        user.name, presence_aware
+    );
+    // A reminder waiting for the user goes back to the regular schedule
+    cancel_deferred_reminder(user.id);
+    reschedule_user(user).await;
+
+    // Update database
+    if let Err(e) = database::update_user_presence_aware(user, presence_aware).await {
+        error!("Failed to update user {} in database: {}", user.name, e);
+    }
+    true
+}
+
+/// Returns false when the user isn't registered
+pub async fn update_user_voice_nudge(user: &User, voice_nudge_minutes: Option<u32>) -> bool {
+    match REGISTRED_USERS.write().await.get_mut(user) {
+        Some(settings) => settings.voice_nudge_minutes = voice_nudge_minutes,
+        None => return false,
+    }
+    info!(
+        "Updated voice nudges for user {} as {:?}",
+        user.name, voice_nudge_minutes
+    );
+
+    // Update database
+    if let Err(e) = database::update_user_voice_nudge(user, voice_nudge_minutes).await {
+        error!("Failed to update user {} in database: {}", user.name, e);
+    }
+    true
+}
+
+/// Sets (or removes, when None) the daily goal, replacing the frequency with its pace over the
+/// active window and disabling the adaptive mode. Returns None when the user isn't registered.
+pub async fn update_user_goal(user: &User, goal: Option<DailyGoal>) -> Option<UserSettings> {
+    let settings = {
+        let mut registered_users = REGISTRED_USERS.write().await;
+        let settings = registered_users.get_mut(user)?;
+        settings.goal = goal;
+        if let Some((goal, window)) = goal.zip(settings.active_window) {
+            settings.frequency = goal.pace(window).frequency;
+            settings.adaptive = None;
+        }
+        settings.clone()
+    };
+    info!(
+        "Updated daily goal for user {} as {:?}, frequency is {:?}",
+        user.name, goal, settings.frequency
+    );
+    reschedule_user(user).await;
+
+    // Update database
+    if let Err(e) = database::update_user_goal(user, goal, settings.frequency).await {
+        error!("Failed to update user {} in database: {}", user.name, e);
+    }
+    Some(settings)
+}
+
+/// Sets (or removes, when None) the profile of the given days, returns false when the user isn't registered
+pub async fn update_user_day_profiles(
+    user: &User,
+    weekdays: &[Weekday],
+    profile: Option<DayProfile>,
+) -> bool {
+    match REGISTRED_USERS.write().await.get_mut(user) {
+        Some(settings) => {
+            for weekday in weekdays {
+                settings.profiles.set(*weekday, profile);
+            }
+        }
+        None => return false,
+    }
+    info!(
+        "Updated profile of {:?} for user {} as {:?}",
+        weekdays, user.name, profile
+    );
+    reschedule_user(user).await;
+
+    // Update database
+    if let Err(e) = database::save_day_profiles(user, weekdays, profile).await {
+        error!("Failed to update user {} in database: {}", user.name, e);
+    }
+    true
+}
+
+pub async fn lookup_registered_user(user_id: UserId) -> Option<User> {
+    REGISTERED_IDS.read().await.get(&user_id).cloned()
+}
+
+pub async fn lookup_registered_users() -> Vec<User> {
+    REGISTRED_USERS.read().await.keys().cloned().collect()
 }
 
 pub async fn lookup_active_reminders_count() -> usize {
@@ -53,21 +826,56 @@ pub async fn lookup_active_reminders_count() -> usize {
 }
 
 pub async fn update_user_to_reminder(user: &User, date: NaiveDateTime) {
+    // A user still awaited when the next reminder is sent ignored the previous one
+    if !AWAITING_ACKNOWLEDGEMENT.write().await.insert(user.clone()) {
+        adjust_adaptive_frequency(user, AdjustmentReason::Ignored, date).await;
+    }
+
     LAST_REMINDED_TIME.write().await.insert(user.clone(), date);
+    // Any snooze has been consumed by this reminder
+    SNOOZED_UNTIL.write().await.remove(user);
     info!(
         "Updated last updated time for user {} as {}",
         user.name, date
     );
+    // A reminder being sent means the pause of the user is over
+    let pause_ended = {
+        let mut paused_users = PAUSED_USERS.write().await;
+        let ended =
+            matches!(paused_users.get(user), Some(PauseState::Until(until)) if *until <= date);
+        if ended {
+            paused_users.remove(user);
+        }
+        ended
+    };
+    // What was drunk so far paces the rest of the day of a daily goal
+    rebalance_user_goal(user, date).await;
+    reschedule_user(user).await;
 
     // Update database
     if let Err(e) = database::update_user_last_reminded(user, date).await {
         error!("Failed to update user {} in database: {}", user.name, e);
     }
+    if pause_ended && let Err(e) = database::update_user_pause(user, None).await {
+        error!("Failed to update user {} in database: {}", user.name, e);
+    }
 }
 
 pub async fn remove_user_from_reminders(user: &User) {
     REGISTRED_USERS.write().await.remove(user);
+    REGISTERED_IDS.write().await.remove(&user.id);
     LAST_REMINDED_TIME.write().await.remove(user);
+    SNOOZED_UNTIL.write().await.remove(user);
+    AWAITING_ACKNOWLEDGEMENT.write().await.remove(user);
+    DELIVERY_FAILURES.write().await.remove(user);
+    SUSPENDED_USERS.write().await.remove(user);
+    REMINDER_THREADS.write().await.remove(user);
+    PAUSED_USERS.write().await.remove(user);
+    ABSENCES.write().await.remove(user);
+    SCHEDULER.unschedule(user);
+    SCHEDULER.schedule_job(Job::WelcomeBack(user.id), user, None);
+    cancel_deferred_reminder(user.id);
+    end_voice_session(user).await;
     info!("Removed {} from registred users", user.name);
 
     // Remove from database
@@ -76,27 +884,62 @@ pub async fn remove_user_from_reminders(user: &User) {
     }
 }
 
-pub async fn load_users_from_database(cache_http: impl CacheHttp) {
-    match database::load_user_reminders().await {
-        Ok((users_data, last_reminded_times_data)) => {
-            use crate::registry::{LAST_REMINDED_TIME, REGISTRED_USERS};
+/// Puts back in memory a user loaded from the database, and schedules its next reminder
+pub async fn restore_user(user: &User, stored_user: &StoredUser) {
+    REGISTRED_USERS
+        .write()
+        .await
+        .insert(user.clone(), stored_user.settings.clone());
+    REGISTERED_IDS.write().await.insert(user.id, user.clone());
+    LAST_REMINDED_TIME
+        .write()
+        .await
+        .insert(user.clone(), stored_user.last_reminded);
+    if let Some(until) = stored_user.snoozed_until {
+        SNOOZED_UNTIL.write().await.insert(user.clone(), until);
+    }
+    if stored_user.awaiting_acknowledgement {
+        AWAITING_ACKNOWLEDGEMENT.write().await.insert(user.clone());
+    }
+    if stored_user.delivery_failures > 0 {
+        DELIVERY_FAILURES
+            .write()
+            .await
+            .insert(user.clone(), stored_user.delivery_failures);
+    }
+    if stored_user.suspended {
+        SUSPENDED_USERS.write().await.insert(user.clone());
+    }
+    if let Some(pause) = stored_user.pause {
+        PAUSED_USERS.write().await.insert(user.clone(), pause);
+    }
+    if !stored_user.absences.is_empty() {
+        ABSENCES
+            .write()
+            .await
+            .insert(user.clone(), stored_user.absences.clone());
+    }
+    if let Some(thread_id) = stored_user.reminder_thread_id {
+        REMINDER_THREADS
+            .write()
+            .await
+            .insert(user.clone(), ChannelId::new(thread_id));
+    }
+    reschedule_user(user).await;
+    reschedule_welcome_back(user).await;
+}
+
+pub async fn load_users_from_database(cache_http: impl CacheHttp, now: NaiveDateTime) {
+    match database::load_user_reminders(now).await {
+        Ok(users_data) => {
             use serenity::all::UserId;
-            use tracing::warn;
             let mut loaded = 0;
-            for (user_data, freq) in users_data.iter() {
+            for (user_data, stored_user) in users_data.iter() {
                 let user_id = UserId::new(user_data.id);
                 match user_id.to_user(&cache_http).await {
                     Ok(user) => {
-                        if let Some(date) = last_reminded_times_data.get(user_data) {
-                            REGISTRED_USERS.write().await.insert(user.clone(), *freq);
-                            LAST_REMINDED_TIME.write().await.insert(user.clone(), *date);
-                            loaded += 1;
-                        } else {
-                            warn!(
-                                "Utilisateur {} (id: {}) ignoré : pas de date de dernier rappel.",
-                                user_data.name, user_data.id
-                            );
-                        }
+                        restore_user(&user, stored_user).await;
+                        loaded += 1;
                     }
                     Err(e) => {
                         warn!(
@@ -120,3 +963,18 @@ pub async fn load_users_from_database(cache_http: impl CacheHttp) {
         }
     }
 }
+
+/// Forgets every user kept in memory, as a restart of the bot would
+#[cfg(test)]
+pub async fn clear_registry() {
+    REGISTRED_USERS.write().await.clear();
+    REGISTERED_IDS.write().await.clear();
+    LAST_REMINDED_TIME.write().await.clear();
+    SNOOZED_UNTIL.write().await.clear();
+    AWAITING_ACKNOWLEDGEMENT.write().await.clear();
+    DELIVERY_FAILURES.write().await.clear();
+    SUSPENDED_USERS.write().await.clear();
+    PAUSED_USERS.write().await.clear();
+    ABSENCES.write().await.clear();
+    REMINDER_THREADS.write().await.clear();
+}
@@ -1,96 +1,573 @@
-use chrono::TimeDelta;
-use serenity::all::{CacheHttp, CreateMessage, User};
-use tracing::{error, info, warn};
-use rand::random_range;
 use crate::data::messages::*;
+use std::{env, future::Future, sync::Arc, time::Duration};
 
+use chrono::NaiveDateTime;
+use rand::random_range;
+use serenity::{
+    Error as SerenityError,
+    all::{ChannelId, CreateAllowedMentions, CreateMessage, GuildId, Http, Mentionable, User},
+};
+use tokio::{sync::watch, task::JoinSet};
+use tracing::{error, info, warn};
+
+use crate::buttons::reminder_buttons::{
+    get_drank_amount_select_menu, get_drank_button, get_skip_button, get_snooze_10min_button,
+    get_snooze_30min_button,
+};
+use crate::catch_up::take_missed_reminders;
+use crate::clock::Clock;
+use crate::delivery::{self, DeliveryTarget};
+use crate::dispatch::{DeliveryFailure, DeliveryOutcome};
+use crate::goal::goal_note;
+use crate::one_off::{lookup_one_off_reminder, remove_one_off_reminder, retry_one_off_reminder};
+use crate::presence::defer_if_unavailable;
 use crate::registry::{
-    LAST_REMINDED_TIME, REGISTRED_USERS, ReminderFrequency, update_user_to_reminder,
+    MAX_CONSECUTIVE_DELIVERY_FAILURES, PauseState, ReminderFrequency, UserSettings,
+    is_user_suspended, lookup_user_absences, lookup_user_pause, lookup_user_settings,
+    postpone_user_reminder, record_delivery_attempt, record_delivery_success,
+    record_permanent_delivery_failure, retry_welcome_back, take_ended_absences,
+    update_user_to_reminder,
 };
+use crate::schedule::next_active_time;
+use crate::scheduler::{Job, SCHEDULER};
+use crate::voice::{format_voice_time, take_due_voice_nudge};
 
+/// Attempts made for a single reminder before giving up until the next one
+const MAX_DELIVERY_ATTEMPTS: u32 = 4;
 
-async fn dm_user_reminder(cache_http: &impl CacheHttp, user: &User, freq: ReminderFrequency) {
-    let content : &'static str = match freq {
-        ReminderFrequency::ThirtyMin => {
-            REMINDER_MESSAGE_THIRTY_MIN.get(random_range(0..(REMINDER_MESSAGE_THIRTY_MIN.len()))).unwrap_or(&ERROR_MESSAGE)
-        },
-        ReminderFrequency::OneHour => {
-            REMINDER_MESSAGE_ONE_HOUR.get(random_range(0..(REMINDER_MESSAGE_ONE_HOUR.len()))).unwrap_or(&ERROR_MESSAGE)
-        },
-        ReminderFrequency::ThreeHours => {
-            REMINDER_MESSAGE_THREE_HOURS.get(random_range(0..(REMINDER_MESSAGE_THREE_HOURS.len()))).unwrap_or(&ERROR_MESSAGE)
-        },
-    };
+/// Delay before the first retry, doubled after each failed attempt
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(2);
 
-    info!("DM'ing user {} for its reminder", user.name);
-    let _res = user
-        .dm(cache_http, CreateMessage::new().content(content))
-        .await;
-    info!("Finished DM'ing user {} for its reminder", user.name);
+/// Deliveries still running this long after a shutdown was requested are aborted. Retries stop
+/// with the shutdown, so it only has to cover the requests in flight on both targets, rate limits
+/// included.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// Carries reminders to the users, Discord itself outside of the scheduling tests
+pub trait Courier: Send + Sync + 'static {
+    fn send_reminder(
+        &self,
+        user: &User,
+        target: DeliveryTarget,
+        content: &str,
+        message: CreateMessage,
+    ) -> impl Future<Output = Result<(), SerenityError>> + Send;
+
+    /// Tells a freshly suspended user why its reminders stopped
+    fn notify_suspension(&self, user: &User) -> impl Future<Output = ()> + Send;
 }
 
-pub async fn walk_reminders(cache_http: impl CacheHttp) {
-    info!("Starting to walk through enabled reminders");
+impl Courier for Http {
+    async fn send_reminder(
+        &self,
+        user: &User,
+        target: DeliveryTarget,
+        content: &str,
+        message: CreateMessage,
+    ) -> Result<(), SerenityError> {
+        delivery::send_reminder(self, user, target, content, message)
+            .await
+            .map(|_| ())
+    }
 
-    let now = chrono::Utc::now().naive_utc();
-    info!("Time is currently : {}", now);
+    async fn notify_suspension(&self, user: &User) {
+        notify_suspension(self, user).await
+    }
+}
 
-    let last_reminded_time_guard = LAST_REMINDED_TIME.read().await;
+/// Waits for `delay` before a retry, returns false when a shutdown is requested meanwhile
+async fn wait_before_retry(delay: Duration, stopping: &watch::Receiver<bool>) -> bool {
+    let mut stopping = stopping.clone();
+    tokio::select! {
+        _ = tokio::time::sleep(delay) => true,
+        Ok(_) = stopping.wait_for(|stopping| *stopping) => false,
+    }
+}
 
-    for (user, last_remind) in last_reminded_time_guard.iter() {
+/// Sends the reminder through `target`, retrying transient failures with an exponential backoff
+/// until a shutdown is requested. Returns the time at which Discord accepted the reminder.
+async fn deliver_with_retries(
+    courier: &impl Courier,
+    clock: &dyn Clock,
+    stopping: &watch::Receiver<bool>,
+    user: &User,
+    target: DeliveryTarget,
+    content: &str,
+    message: &CreateMessage,
+) -> Result<NaiveDateTime, DeliveryFailure> {
+    let mut retry_delay = INITIAL_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
         info!(
-            "User {} last remind is at {} with a frequency of {:?}",
-            user.name,
-            last_remind,
-            REGISTRED_USERS.read().await.get(user)
+            "Sending reminder to user {} through {:?} (attempt {})",
+            user.name, target, attempt
         );
-    }
+        let res = courier
+            .send_reminder(user, target, content, message.clone())
+            .await;
+        let attempted_at = clock.now();
 
-    let mut usernames_reminded = Vec::<String>::new();
+        let why = match res {
+            Ok(_) => {
+                record_delivery_attempt(
+                    user,
+                    attempted_at,
+                    attempt,
+                    target,
+                    DeliveryOutcome::Delivered,
+                    None,
+                )
+                .await;
+                info!("Delivered reminder to user {}", user.name);
+                return Ok(attempted_at);
+            }
+            Err(why) => why,
+        };
 
-    for (user, freq) in REGISTRED_USERS.read().await.iter() {
-        info!("Checking for user {}", user.name);
+        let failure = DeliveryFailure::classify(&why);
+        record_delivery_attempt(
+            user,
+            attempted_at,
+            attempt,
+            target,
+            DeliveryOutcome::Failed(failure),
+            Some(why.to_string()),
+        )
+        .await;
 
-        let Some(last_reminded) = last_reminded_time_guard.get(user) else {
+        if failure == DeliveryFailure::Permanent || attempt >= MAX_DELIVERY_ATTEMPTS {
             warn!(
-                "User {} has no last reminded time :(, skipping...",
+                "Could not deliver reminder to user {} through {:?} ({:?} failure) : {}",
+                user.name, target, failure, why
+            );
+            return Err(failure);
+        }
+
+        warn!(
+            "Failed to deliver reminder to user {}, retrying in {:?} : {}",
+            user.name, retry_delay, why
+        );
+        if !wait_before_retry(retry_delay, stopping).await {
+            warn!(
+                "Shutting down, giving up on the reminder of user {} for now",
                 user.name
             );
-            continue;
-        };
+            return Err(failure);
+        }
+        retry_delay *= 2;
+        attempt += 1;
+    }
+}
 
-        let delta = TimeDelta::from(*freq);
-        info!("Created time delta from user preferred reminder frequency");
+/// Sends the reminder to the chosen target, falling back to the other one when the first
+/// can't be reached. Returns the time at which Discord accepted the reminder.
+async fn dm_user_reminder(
+    courier: &impl Courier,
+    clock: &dyn Clock,
+    stopping: &watch::Receiver<bool>,
+    user: &User,
+    settings: &UserSettings,
+    notes: &[String],
+) -> Result<NaiveDateTime, DeliveryFailure> {
+    let messages: &[&'static str] = match settings.frequency {
+        ReminderFrequency::ThirtyMin => &REMINDER_MESSAGE_THIRTY_MIN,
+        ReminderFrequency::OneHour => &REMINDER_MESSAGE_ONE_HOUR,
+        ReminderFrequency::ThreeHours => &REMINDER_MESSAGE_THREE_HOURS,
+        // Custom intervals borrow the messages of the closest preset
+        ReminderFrequency::Custom(minutes) if minutes < 60 => &REMINDER_MESSAGE_THIRTY_MIN,
+        ReminderFrequency::Custom(minutes) if minutes < 3 * 60 => &REMINDER_MESSAGE_ONE_HOUR,
+        ReminderFrequency::Custom(_) => &REMINDER_MESSAGE_THREE_HOURS,
+    };
+    let content: &'static str = messages
+        .get(random_range(0..messages.len()))
+        .unwrap_or(&ERROR_MESSAGE);
+    let content = notes.iter().fold(content.to_string(), |content, note| {
+        format!("{content}\n*{note}*")
+    });
 
-        let Some(limit) = last_reminded.checked_add_signed(delta) else {
-            error!(
-                "Unable to add {} with {} to check for reminders of user {}",
-                last_reminded, delta, user
-            );
-            continue;
-        };
-        info!("Limit time has been computed");
+    let message = CreateMessage::new()
+        .button(get_drank_button())
+        .button(get_snooze_10min_button())
+        .button(get_snooze_30min_button())
+        .button(get_skip_button())
+        .select_menu(get_drank_amount_select_menu());
 
-        if limit < now {
-            dm_user_reminder(&cache_http, user, *freq).await;
-            usernames_reminded.push(user.name.clone());
-        } else {
+    deliver_with_fallback(
+        courier,
+        clock,
+        stopping,
+        user,
+        (settings.delivery, settings.fallback),
+        &content,
+        &message,
+    )
+    .await
+}
+
+/// Sends the reminder to `delivery`, falling back to `fallback` when the first can't be reached
+async fn deliver_with_fallback(
+    courier: &impl Courier,
+    clock: &dyn Clock,
+    stopping: &watch::Receiver<bool>,
+    user: &User,
+    (delivery, fallback): (DeliveryTarget, Option<DeliveryTarget>),
+    content: &str,
+    message: &CreateMessage,
+) -> Result<NaiveDateTime, DeliveryFailure> {
+    let res =
+        deliver_with_retries(courier, clock, stopping, user, delivery, content, message).await;
+    match (res, fallback) {
+        (Err(DeliveryFailure::Permanent), Some(fallback)) if fallback != delivery => {
             info!(
-                "User {} time limit for reminder is not passed yet : {}",
-                user.name, limit
+                "Falling back to {:?} for the reminder of user {}",
+                fallback, user.name
             );
+            deliver_with_retries(courier, clock, stopping, user, fallback, content, message).await
+        }
+        (res, _) => res,
+    }
+}
+
+/// `DISCORD_NOTICE_CHANNEL_ID` when set, the system channel of the guild otherwise
+async fn notice_channel(http: &Http) -> Option<ChannelId> {
+    if let Ok(Ok(channel_id)) = env::var("DISCORD_NOTICE_CHANNEL_ID").map(|it| it.parse::<u64>()) {
+        return Some(ChannelId::new(channel_id));
+    }
+
+    let Ok(Ok(guild_id)) = env::var("DISCORD_GUILD_ID").map(|it| it.parse::<u64>()) else {
+        return None;
+    };
+    match GuildId::new(guild_id).to_partial_guild(http).await {
+        Ok(guild) => guild.system_channel_id,
+        Err(why) => {
+            error!(
+                "Cannot fetch the guild to find its system channel : {}",
+                why
+            );
+            None
+        }
+    }
+}
+
+/// Tells a freshly suspended user, in the guild, why its reminders stopped and how to resume them
+async fn notify_suspension(http: &Http, user: &User) {
+    let Some(channel_id) = notice_channel(http).await else {
+        warn!("No channel to tell user {} about its suspension", user.name);
+        return;
+    };
+
+    let content = format!(
+        "{} je n'arrive plus à t'envoyer de messages privés, tes rappels sont donc suspendus. 🚱\n\
+        Pour les réactiver, autorise les messages privés des membres de ce serveur \
+        (Paramètres de confidentialité du serveur) et vérifie que le bot n'est pas bloqué, \
+        puis utilise à nouveau `/register`.",
+        user.mention()
+    );
+    let message = CreateMessage::new()
+        .content(content)
+        .allowed_mentions(CreateAllowedMentions::new().users([user.id]));
+
+    if let Err(why) = channel_id.send_message(http, message).await {
+        error!(
+            "Cannot tell user {} about its suspension : {}",
+            user.name, why
+        );
+    }
+}
+
+/// Registry guards are only held while copying the settings, never across the DM itself
+async fn send_due_reminder(
+    courier: &impl Courier,
+    clock: &dyn Clock,
+    stopping: &watch::Receiver<bool>,
+    user: &User,
+    now: NaiveDateTime,
+) {
+    let Some(settings) = lookup_user_settings(user).await else {
+        warn!("User {} is due but not registered anymore", user.name);
+        return;
+    };
+
+    if settings.presence_aware && defer_if_unavailable(user) {
+        info!(
+            "User {} is offline or busy, its reminder waits for it to come back",
+            user.name
+        );
+        return;
+    }
+
+    // Only a reminder accepted by Discord counts as sent
+    let mut notes = Vec::new();
+    if let Some(missed) = take_missed_reminders(user).await
+        && missed > 1
+    {
+        notes.push(format!(
+            "Pendant que j'étais hors ligne, tu as manqué {missed} rappels."
+        ));
+    }
+    if let Some(note) = goal_note(user, &settings, now).await {
+        notes.push(note);
+    }
+
+    match dm_user_reminder(courier, clock, stopping, user, &settings, &notes).await {
+        Ok(delivered_at) => {
+            record_delivery_success(user).await;
+            update_user_to_reminder(user, delivered_at).await;
+        }
+        Err(DeliveryFailure::Permanent) => {
+            if record_permanent_delivery_failure(user).await {
+                info!(
+                    "User {} failed {} deliveries in a row, telling them in the guild",
+                    user.name, MAX_CONSECUTIVE_DELIVERY_FAILURES
+                );
+                courier.notify_suspension(user).await;
+            } else {
+                postpone_user_reminder(user, now).await;
+            }
         }
+        Err(DeliveryFailure::Transient) => postpone_user_reminder(user, now).await,
     }
+}
 
-    info!("Dropping last reminded time guard");
-    drop(last_reminded_time_guard);
+/// Sends a one-off reminder through the targets chosen at registration, or as a DM when the user
+/// isn't registered. It is dropped once delivered or when it can't ever be, and tried again later
+/// after a transient failure.
+async fn send_one_off_reminder(
+    courier: &impl Courier,
+    clock: &dyn Clock,
+    stopping: &watch::Receiver<bool>,
+    id: i64,
+) {
+    let Some(reminder) = lookup_one_off_reminder(id).await else {
+        return;
+    };
+    let user = &reminder.user;
+    info!(
+        "Sending one-off reminder {} of user {}, due at {}",
+        id, user.name, reminder.due_at
+    );
 
-    info!("Updating every single reminder time after the scan");
-    for user in REGISTRED_USERS.read().await.keys() {
-        if usernames_reminded.contains(&user.name) {
-            update_user_to_reminder(user, now).await;
+    let content = match &reminder.note {
+        Some(note) => format!("⏰ Tu m'as demandé de te rappeler de boire de l'eau : *{note}* 💧"),
+        None => "⏰ Tu m'as demandé de te rappeler de boire de l'eau ! 💧".to_string(),
+    };
+    let targets = match lookup_user_settings(user).await {
+        Some(settings) => (settings.delivery, settings.fallback),
+        None => (DeliveryTarget::DirectMessage, None),
+    };
+
+    let res = deliver_with_fallback(
+        courier,
+        clock,
+        stopping,
+        user,
+        targets,
+        &content,
+        &CreateMessage::new(),
+    )
+    .await;
+    match res {
+        Ok(_) => remove_one_off_reminder(id).await,
+        Err(DeliveryFailure::Permanent) => {
+            warn!("One-off reminder {} of user {} was dropped", id, user.name);
+            remove_one_off_reminder(id).await;
         }
+        Err(DeliveryFailure::Transient) => retry_one_off_reminder(id, clock.now()).await,
     }
+}
 
-    info!("Finished scanning &/ processing all the reminders");
+/// Welcomes a user back through its usual targets once its absence is over. The absence is only
+/// forgotten once Discord accepted the message, or when it can't ever be delivered.
+async fn send_welcome_back(
+    courier: &impl Courier,
+    clock: &dyn Clock,
+    stopping: &watch::Receiver<bool>,
+    user: &User,
+    now: NaiveDateTime,
+) {
+    let Some(settings) = lookup_user_settings(user).await else {
+        return;
+    };
+
+    // Back-to-back absences, and suspended users, go without a welcome back
+    let absent = lookup_user_absences(user)
+        .await
+        .iter()
+        .any(|absence| absence.contains(settings.timezone, now));
+    if absent || is_user_suspended(user).await {
+        take_ended_absences(user, now).await;
+        return;
+    }
+    // Like after a restart, a welcome back due in quiet hours waits for the window
+    if let Some(opening) = next_active_time(&settings, now)
+        && opening > now
+    {
+        SCHEDULER.schedule_job(Job::WelcomeBack(user.id), user, Some(opening));
+        return;
+    }
+
+    info!("Absence of user {} is over, welcoming them back", user.name);
+    let content = match lookup_user_pause(user).await {
+        Some(PauseState::Indefinite) => {
+            "🌴 Bon retour de vacances ! Tes rappels sont toujours en pause, utilise `/resume` pour les reprendre."
+                .to_string()
+        }
+        Some(PauseState::Until(until)) if now < until => format!(
+            "🌴 Bon retour de vacances ! Tes rappels reprendront à la fin de ta pause, le {}.",
+            settings.local_time(until).format("%d/%m à %H:%M")
+        ),
+        _ => "🌴 Bon retour de vacances ! Les rappels reprennent.".to_string(),
+    };
+    let res = deliver_with_fallback(
+        courier,
+        clock,
+        stopping,
+        user,
+        (settings.delivery, settings.fallback),
+        &content,
+        &CreateMessage::new(),
+    )
+    .await;
+    match res {
+        Ok(_) => {
+            take_ended_absences(user, now).await;
+        }
+        Err(DeliveryFailure::Permanent) => {
+            warn!("Welcome back of user {} was dropped", user.name);
+            take_ended_absences(user, now).await;
+        }
+        Err(DeliveryFailure::Transient) => retry_welcome_back(user, clock.now()),
+    }
+}
+
+/// Nudges a user who has been in voice for a while, through its usual targets
+async fn send_voice_nudge(
+    courier: &impl Courier,
+    clock: &dyn Clock,
+    stopping: &watch::Receiver<bool>,
+    user: &User,
+    now: NaiveDateTime,
+) {
+    let Some(time_in_voice) = take_due_voice_nudge(user, now).await else {
+        return;
+    };
+    let Some(settings) = lookup_user_settings(user).await else {
+        return;
+    };
+    info!(
+        "User {} has been in voice for {}, sending a nudge",
+        user.name,
+        format_voice_time(time_in_voice)
+    );
+
+    let content = format!(
+        "🎧 Ça fait {} que tu es en vocal, pense à boire un verre d'eau ! 💧",
+        format_voice_time(time_in_voice)
+    );
+    let res = deliver_with_fallback(
+        courier,
+        clock,
+        stopping,
+        user,
+        (settings.delivery, settings.fallback),
+        &content,
+        &CreateMessage::new(),
+    )
+    .await;
+    if res.is_err() {
+        warn!("Voice nudge of user {} was dropped", user.name);
+    }
+}
+
+/// Hands every job due at `now` to its own delivery task, returns the amount of jobs dispatched.
+/// The deliveries stop retrying once `stopping` turns true.
+pub fn dispatch_due_jobs<C: Courier>(
+    deliveries: &mut JoinSet<()>,
+    courier: &Arc<C>,
+    clock: &Arc<dyn Clock>,
+    stopping: &watch::Receiver<bool>,
+    now: NaiveDateTime,
+) -> usize {
+    let due_jobs = SCHEDULER.pop_due(now);
+    if due_jobs.is_empty() {
+        return 0;
+    }
+
+    info!("{} reminders are due at {}", due_jobs.len(), now);
+    let count = due_jobs.len();
+    for (job, user) in due_jobs {
+        let courier = Arc::clone(courier);
+        let clock = Arc::clone(clock);
+        let stopping = stopping.clone();
+        match job {
+            Job::Reminder(_) => deliveries.spawn(async move {
+                send_due_reminder(&*courier, &*clock, &stopping, &user, now).await
+            }),
+            Job::OneOff(id) => deliveries.spawn(async move {
+                send_one_off_reminder(&*courier, &*clock, &stopping, id).await
+            }),
+            Job::VoiceNudge(_) => deliveries.spawn(async move {
+                send_voice_nudge(&*courier, &*clock, &stopping, &user, now).await
+            }),
+            Job::WelcomeBack(_) => deliveries.spawn(async move {
+                send_welcome_back(&*courier, &*clock, &stopping, &user, now).await
+            }),
+        };
+    }
+    count
+}
+
+/// Sleeps until the earliest reminder is due, hands every due reminder to its own delivery task,
+/// and starts over without waiting for the deliveries to finish.
+///
+/// Once `shutdown` resolves, no more reminders are sent and the ones in flight are given some
+/// time to finish without retrying, so a reminder sent is also recorded as sent.
+pub async fn run_reminders<C: Courier>(
+    courier: Arc<C>,
+    clock: Arc<dyn Clock>,
+    shutdown: impl Future<Output = ()>,
+) {
+    info!(
+        "Reminder loop started with {} scheduled reminders",
+        SCHEDULER.scheduled_count()
+    );
+
+    tokio::pin!(shutdown);
+    let (stop_retries, stopping) = watch::channel(false);
+    let mut deliveries = JoinSet::new();
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = SCHEDULER.wait_for_next_due(&*clock) => {}
+            Some(res) = deliveries.join_next() => {
+                if let Err(why) = res {
+                    error!("A reminder delivery task failed : {}", why);
+                }
+                continue;
+            }
+        }
+
+        dispatch_due_jobs(&mut deliveries, &courier, &clock, &stopping, clock.now());
+    }
+
+    stop_retries.send_replace(true);
+    info!(
+        "Reminder loop stopped, waiting for {} deliveries in flight",
+        deliveries.len()
+    );
+    let drained = tokio::time::timeout(SHUTDOWN_GRACE_PERIOD, async {
+        while let Some(res) = deliveries.join_next().await {
+            if let Err(why) = res {
+                error!("A reminder delivery task failed : {}", why);
+            }
+        }
+    })
+    .await;
+    if drained.is_err() {
+        warn!(
+            "{} deliveries did not finish within {:?} and were aborted",
+            deliveries.len(),
+            SHUTDOWN_GRACE_PERIOD
+        );
+    }
 }
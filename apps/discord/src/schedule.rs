@@ -0,0 +1,710 @@
+use chrono::{Datelike, Days, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Weekday};
+use chrono_tz::Tz;
+use cron::Schedule;
+
+use crate::registry::{MIN_CUSTOM_FREQUENCY_MINUTES, ReminderFrequency, UserSettings};
+
+/// Fire times checked for the minimal gap between two reminders when validating a cron schedule
+const CRON_VALIDATION_SAMPLE: usize = 500;
+
+/// Local hours of the day during which a user accepts to be reminded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl ActiveWindow {
+    /// Returns None for empty windows, where the start and the end are the same
+    pub fn new(start: NaiveTime, end: NaiveTime) -> Option<Self> {
+        (start != end).then_some(Self { start, end })
+    }
+
+    /// Windows ending before they start span over midnight, like 22:00-02:00
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+impl std::fmt::Display for ActiveWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} - {}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+/// Schedule of a single day of the week, overriding the user's default frequency and window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DayProfile {
+    Off,
+    On {
+        frequency: ReminderFrequency,
+        active_window: Option<ActiveWindow>,
+    },
+}
+
+impl std::fmt::Display for DayProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DayProfile::Off => write!(f, "aucun rappel"),
+            DayProfile::On {
+                frequency,
+                active_window: Some(window),
+            } => write!(f, "toutes les {frequency}, entre {window}"),
+            DayProfile::On {
+                frequency,
+                active_window: None,
+            } => write!(f, "toutes les {frequency}, à toute heure"),
+        }
+    }
+}
+
+/// Day profiles of a user, days without a profile follow the default schedule
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WeekProfiles([Option<DayProfile>; 7]);
+
+impl WeekProfiles {
+    pub fn get(&self, weekday: Weekday) -> Option<DayProfile> {
+        self.0[weekday.num_days_from_monday() as usize]
+    }
+
+    pub fn set(&mut self, weekday: Weekday, profile: Option<DayProfile>) {
+        self.0[weekday.num_days_from_monday() as usize] = profile;
+    }
+}
+
+pub const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+pub fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "Lundi",
+        Weekday::Tue => "Mardi",
+        Weekday::Wed => "Mercredi",
+        Weekday::Thu => "Jeudi",
+        Weekday::Fri => "Vendredi",
+        Weekday::Sat => "Samedi",
+        Weekday::Sun => "Dimanche",
+    }
+}
+
+/// A schedule defined by a cron expression with seconds, like `0 */45 9-18 * * MON-FRI`, evaluated
+/// in the user's time zone. It replaces the frequency, the active window and the day profiles.
+#[derive(Debug, Clone)]
+pub struct CronSchedule {
+    expression: String,
+    schedule: Schedule,
+}
+
+impl CronSchedule {
+    /// Rejects invalid expressions, and schedules that never fire or fire too often, with an
+    /// explanation for the user
+    pub fn parse(expression: &str, timezone: Tz, now: NaiveDateTime) -> Result<Self, String> {
+        let expression = expression.trim();
+        let schedule = expression.parse::<Schedule>().map_err(|e| {
+            format!(
+                "L'expression cron `{expression}` est invalide ({e}), elle doit avoir 6 champs : seconde minute heure jour mois jour-de-la-semaine"
+            )
+        })?;
+
+        let cron = Self {
+            expression: expression.to_string(),
+            schedule,
+        };
+        let fire_times: Vec<_> = cron
+            .upcoming(timezone, now)
+            .take(CRON_VALIDATION_SAMPLE)
+            .collect();
+        if fire_times.is_empty() {
+            return Err(format!(
+                "L'expression cron `{expression}` ne se déclenche jamais"
+            ));
+        }
+        let min_gap = TimeDelta::minutes(MIN_CUSTOM_FREQUENCY_MINUTES as i64);
+        if fire_times
+            .windows(2)
+            .any(|pair| pair[1] - pair[0] < min_gap)
+        {
+            return Err(format!(
+                "L'expression cron `{expression}` se déclenche trop souvent, les rappels doivent être espacés d'au moins {MIN_CUSTOM_FREQUENCY_MINUTES} minutes"
+            ));
+        }
+
+        Ok(cron)
+    }
+
+    /// Restores a schedule saved in the database, without the checks made when it was chosen
+    pub fn from_db(expression: &str) -> Option<Self> {
+        let schedule = expression.parse::<Schedule>().ok()?;
+        Some(Self {
+            expression: expression.to_string(),
+            schedule,
+        })
+    }
+
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    /// Fire times strictly after `after`, in UTC
+    pub fn upcoming(
+        &self,
+        timezone: Tz,
+        after: NaiveDateTime,
+    ) -> impl Iterator<Item = NaiveDateTime> + '_ {
+        self.schedule
+            .after(&timezone.from_utc_datetime(&after))
+            .map(|time| time.naive_utc())
+    }
+}
+
+/// Effective schedule of a given day, None when reminders are off that day
+fn day_schedule(
+    settings: &UserSettings,
+    weekday: Weekday,
+) -> Option<(ReminderFrequency, Option<ActiveWindow>)> {
+    match settings.profiles.get(weekday) {
+        Some(DayProfile::Off) => None,
+        Some(DayProfile::On {
+            frequency,
+            active_window,
+        }) => Some((frequency, active_window)),
+        None => Some((settings.frequency, settings.active_window)),
+    }
+}
+
+/// Accepts `9:30`, `09:30`, `9h30` and `9h`
+pub fn parse_time_of_day(value: &str) -> Option<NaiveTime> {
+    let value = value.trim();
+    ["%H:%M", "%Hh%M", "%Hh"]
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(value, format).ok())
+}
+
+/// Days off during which no reminder is sent, both bounds included, in the user's time zone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Absence {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl Absence {
+    /// Rejects absences ending before they start
+    pub fn new(start: NaiveDate, end: NaiveDate) -> Option<Self> {
+        (start <= end).then_some(Self { start, end })
+    }
+
+    /// Beginning of the first day, in UTC
+    pub fn starts_at(&self, timezone: Tz) -> NaiveDateTime {
+        local_to_utc(timezone, self.start.and_time(NaiveTime::MIN))
+    }
+
+    /// End of the last day, in UTC
+    pub fn ends_at(&self, timezone: Tz) -> NaiveDateTime {
+        let day_after = self.end.succ_opt().unwrap_or(self.end);
+        local_to_utc(timezone, day_after.and_time(NaiveTime::MIN))
+    }
+
+    pub fn contains(&self, timezone: Tz, time: NaiveDateTime) -> bool {
+        self.starts_at(timezone) <= time && time < self.ends_at(timezone)
+    }
+}
+
+impl std::fmt::Display for Absence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "du {} au {}",
+            self.start.format("%d/%m/%Y"),
+            self.end.format("%d/%m/%Y")
+        )
+    }
+}
+
+/// Parses a date such as `24/12/2025`, `2025-12-24` or `24/12`, the latter being in the year of
+/// `today` or the next one when already past
+pub fn parse_date(value: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let value = value.trim();
+    if let Some(date) = ["%d/%m/%Y", "%Y-%m-%d"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+    {
+        return Some(date);
+    }
+
+    let (day, month) = value.split_once('/')?;
+    let (day, month) = (day.parse().ok()?, month.parse().ok()?);
+    let date = NaiveDate::from_ymd_opt(today.year(), month, day)?;
+    if date >= today {
+        Some(date)
+    } else {
+        NaiveDate::from_ymd_opt(today.year() + 1, month, day)
+    }
+}
+
+/// Parses a duration such as `45m`, `2h`, `1h30` or `3j`, a bare number being minutes
+pub fn parse_duration(value: &str) -> Option<TimeDelta> {
+    let value = value.trim().to_lowercase().replace(' ', "");
+    if let Ok(minutes) = value.parse::<i64>() {
+        return TimeDelta::try_minutes(minutes).filter(|_| minutes > 0);
+    }
+
+    let mut total = TimeDelta::zero();
+    let mut rest = value.as_str();
+    let mut last_unit = None;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let amount: i64 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+
+        let unit = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let delta = match &rest[..unit] {
+            "j" | "d" => TimeDelta::try_days(amount)?,
+            "h" => TimeDelta::try_hours(amount)?,
+            "m" | "min" => TimeDelta::try_minutes(amount)?,
+            // The minutes of `1h30` have no unit
+            "" if last_unit == Some("h") => TimeDelta::try_minutes(amount)?,
+            _ => return None,
+        };
+        last_unit = Some(&rest[..unit]);
+        rest = &rest[unit..];
+        total = total.checked_add(&delta)?;
+    }
+
+    (total > TimeDelta::zero()).then_some(total)
+}
+
+/// Converts a local time to UTC, using the earliest instant when the time is ambiguous and
+/// skipping forward when it falls in a daylight-saving gap
+pub fn local_to_utc(timezone: Tz, local: NaiveDateTime) -> NaiveDateTime {
+    timezone
+        .from_local_datetime(&local)
+        .earliest()
+        .or_else(|| {
+            timezone
+                .from_local_datetime(&(local + TimeDelta::hours(1)))
+                .earliest()
+        })
+        .map(|time| time.naive_utc())
+        .unwrap_or(local)
+}
+
+/// Computes when the next reminder is due, in UTC, or None when every day of the week is off.
+/// Users with a cron schedule are reminded at its next fire time instead.
+///
+/// The interval is the one of the day the last reminder was sent. A reminder falling in quiet
+/// hours, or on a day off, is pushed to the next opening of an active window, so a single
+/// reminder is sent when the window opens rather than the ones missed in between.
+pub fn next_reminder_time(
+    settings: &UserSettings,
+    last_reminded: NaiveDateTime,
+) -> Option<NaiveDateTime> {
+    if let Some(cron) = &settings.cron {
+        return cron.upcoming(settings.timezone, last_reminded).next();
+    }
+
+    let last_weekday = settings.local_time(last_reminded).weekday();
+    let frequency = day_schedule(settings, last_weekday)
+        .map(|(frequency, _)| frequency)
+        .unwrap_or(settings.frequency);
+
+    let candidate = last_reminded.checked_add_signed(TimeDelta::from(frequency))?;
+    next_active_time(settings, candidate)
+}
+
+/// Returns the first time, in UTC, at or after `candidate` at which reminders may be sent, or
+/// None when every day of the week is off
+pub fn next_active_time(
+    settings: &UserSettings,
+    candidate: NaiveDateTime,
+) -> Option<NaiveDateTime> {
+    // Cron schedules fire at their own times, the first one at or after the candidate
+    if let Some(cron) = &settings.cron {
+        let just_before = candidate.checked_sub_signed(TimeDelta::seconds(1))?;
+        return cron.upcoming(settings.timezone, just_before).next();
+    }
+
+    let local = settings.local_time(candidate).naive_local();
+
+    for offset in 0..=WEEKDAYS.len() as u64 {
+        let day = local.date() + Days::new(offset);
+        let Some((_, active_window)) = day_schedule(settings, day.weekday()) else {
+            continue;
+        };
+
+        let opening = match active_window {
+            None if offset == 0 => return Some(candidate),
+            None => day.and_time(NaiveTime::MIN),
+            Some(window) if offset == 0 && window.contains(local.time()) => {
+                return Some(candidate);
+            }
+            Some(window) => day.and_time(window.start),
+        };
+
+        if opening >= local {
+            return Some(local_to_utc(settings.timezone, opening));
+        }
+    }
+
+    None
+}
+
+/// Counts the reminders due from `first_due` up to `now`, stopping at `limit`
+pub fn missed_reminders(
+    settings: &UserSettings,
+    first_due: NaiveDateTime,
+    now: NaiveDateTime,
+    limit: u32,
+) -> u32 {
+    let mut missed = 0;
+    let mut due = Some(first_due);
+    while let Some(time) = due
+        && time <= now
+        && missed < limit
+    {
+        missed += 1;
+        due = next_reminder_time(settings, time);
+    }
+    missed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(time: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(time, "%H:%M").unwrap()
+    }
+
+    fn utc(time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    fn window(start: &str, end: &str) -> ActiveWindow {
+        ActiveWindow::new(time(start), time(end)).unwrap()
+    }
+
+    /// Hourly reminders in Paris, UTC+2 in summer, with the given window
+    fn settings(active_window: Option<ActiveWindow>) -> UserSettings {
+        UserSettings {
+            active_window,
+            ..UserSettings::new(ReminderFrequency::OneHour, Tz::Europe__Paris)
+        }
+    }
+
+    #[test]
+    fn window_contains_its_start_but_not_its_end() {
+        let window = window("09:00", "19:00");
+        assert!(window.contains(time("09:00")));
+        assert!(window.contains(time("18:59")));
+        assert!(!window.contains(time("19:00")));
+        assert!(!window.contains(time("08:59")));
+        assert!(ActiveWindow::new(time("09:00"), time("09:00")).is_none());
+    }
+
+    #[test]
+    fn window_spans_over_midnight() {
+        let window = window("22:00", "02:00");
+        assert!(window.contains(time("22:00")));
+        assert!(window.contains(time("23:30")));
+        assert!(window.contains(time("00:00")));
+        assert!(window.contains(time("01:59")));
+        assert!(!window.contains(time("02:00")));
+        assert!(!window.contains(time("12:00")));
+    }
+
+    #[test]
+    fn next_active_time_at_the_window_edges() {
+        let settings = settings(Some(window("09:00", "19:00")));
+
+        // 08:59 in Paris waits for the opening, 09:00 and 18:59 are inside the window
+        assert_eq!(
+            next_active_time(&settings, utc("2025-06-02 06:59")),
+            Some(utc("2025-06-02 07:00"))
+        );
+        assert_eq!(
+            next_active_time(&settings, utc("2025-06-02 07:00")),
+            Some(utc("2025-06-02 07:00"))
+        );
+        assert_eq!(
+            next_active_time(&settings, utc("2025-06-02 16:59")),
+            Some(utc("2025-06-02 16:59"))
+        );
+        // 19:00 is already closed, the next opening is the morning after
+        assert_eq!(
+            next_active_time(&settings, utc("2025-06-02 17:00")),
+            Some(utc("2025-06-03 07:00"))
+        );
+    }
+
+    #[test]
+    fn next_active_time_in_a_window_over_midnight() {
+        let settings = settings(Some(window("22:00", "02:00")));
+
+        assert_eq!(
+            next_active_time(&settings, utc("2025-06-02 23:30")),
+            Some(utc("2025-06-02 23:30"))
+        );
+        assert_eq!(
+            next_active_time(&settings, utc("2025-06-03 00:00")),
+            Some(utc("2025-06-03 20:00"))
+        );
+    }
+
+    #[test]
+    fn next_active_time_skips_days_off() {
+        let mut settings = settings(Some(window("09:00", "19:00")));
+        settings.profiles.set(Weekday::Tue, Some(DayProfile::Off));
+
+        // Monday evening, Tuesday is off
+        assert_eq!(
+            next_active_time(&settings, utc("2025-06-02 18:00")),
+            Some(utc("2025-06-04 07:00"))
+        );
+
+        for weekday in WEEKDAYS {
+            settings.profiles.set(weekday, Some(DayProfile::Off));
+        }
+        assert_eq!(next_active_time(&settings, utc("2025-06-02 18:00")), None);
+    }
+
+    #[test]
+    fn day_profiles_replace_the_interval_and_window() {
+        let mut settings = settings(Some(window("09:00", "19:00")));
+        settings.profiles.set(
+            Weekday::Sat,
+            Some(DayProfile::On {
+                frequency: ReminderFrequency::ThreeHours,
+                active_window: Some(window("10:00", "20:00")),
+            }),
+        );
+
+        // Monday follows the default hourly schedule, Saturday its own one
+        assert_eq!(
+            next_reminder_time(&settings, utc("2025-06-02 07:00")),
+            Some(utc("2025-06-02 08:00"))
+        );
+        assert_eq!(
+            next_reminder_time(&settings, utc("2025-06-07 08:00")),
+            Some(utc("2025-06-07 11:00"))
+        );
+        // 08:30 on Saturday is before its window, although within the default one
+        assert_eq!(
+            next_active_time(&settings, utc("2025-06-07 06:30")),
+            Some(utc("2025-06-07 08:00"))
+        );
+    }
+
+    #[test]
+    fn days_off_hand_over_to_the_next_active_day() {
+        let mut settings = settings(Some(window("10:00", "20:00")));
+        settings.profiles.set(Weekday::Sun, Some(DayProfile::Off));
+        settings.profiles.set(
+            Weekday::Mon,
+            Some(DayProfile::On {
+                frequency: ReminderFrequency::Custom(90),
+                active_window: Some(window("08:00", "18:00")),
+            }),
+        );
+
+        // 20:30 on Saturday is past the window, Sunday is off, Monday opens at 08:00
+        let monday = next_reminder_time(&settings, utc("2025-06-07 17:30"));
+        assert_eq!(monday, Some(utc("2025-06-09 06:00")));
+        assert_eq!(
+            next_reminder_time(&settings, monday.unwrap()),
+            Some(utc("2025-06-09 07:30"))
+        );
+        assert_eq!(
+            missed_reminders(
+                &settings,
+                utc("2025-06-07 17:30"),
+                utc("2025-06-09 05:59"),
+                10
+            ),
+            1
+        );
+    }
+
+    #[test]
+    fn absences_cover_whole_days_in_the_user_zone() {
+        let absence = Absence::new(date("2025-06-10"), date("2025-06-12")).unwrap();
+        let paris = Tz::Europe__Paris;
+
+        // Midnight in Paris is 22:00 UTC the day before in summer
+        assert_eq!(absence.starts_at(paris), utc("2025-06-09 22:00"));
+        assert_eq!(absence.ends_at(paris), utc("2025-06-12 22:00"));
+        assert!(!absence.contains(paris, utc("2025-06-09 21:59")));
+        assert!(absence.contains(paris, utc("2025-06-09 22:00")));
+        assert!(absence.contains(paris, utc("2025-06-12 21:59")));
+        assert!(!absence.contains(paris, utc("2025-06-12 22:00")));
+        assert!(absence.contains(Tz::UTC, utc("2025-06-12 23:00")));
+
+        assert!(Absence::new(date("2025-06-12"), date("2025-06-10")).is_none());
+    }
+
+    #[test]
+    fn absences_over_a_daylight_saving_change() {
+        // Paris goes from UTC+1 to UTC+2 during the night of 2025-03-30
+        let absence = Absence::new(date("2025-03-29"), date("2025-03-30")).unwrap();
+        let paris = Tz::Europe__Paris;
+        assert_eq!(absence.starts_at(paris), utc("2025-03-28 23:00"));
+        assert_eq!(absence.ends_at(paris), utc("2025-03-30 22:00"));
+    }
+
+    #[test]
+    fn window_opening_in_a_daylight_saving_gap() {
+        // Clocks go from 02:00 to 03:00 in Paris on 2025-03-30, 02:30 doesn't exist that day
+        let settings = settings(Some(window("02:30", "06:00")));
+        assert_eq!(
+            next_active_time(&settings, utc("2025-03-30 00:00")),
+            Some(utc("2025-03-30 01:30"))
+        );
+    }
+
+    #[test]
+    fn window_opening_twice_on_a_daylight_saving_end() {
+        // Clocks go from 03:00 back to 02:00 in Paris on 2025-10-26, the first 02:30 is used
+        let settings = settings(Some(window("02:30", "06:00")));
+        assert_eq!(
+            next_active_time(&settings, utc("2025-10-25 22:00")),
+            Some(utc("2025-10-26 00:30"))
+        );
+    }
+
+    #[test]
+    fn reminders_missed_in_quiet_hours_are_not_sent_when_the_window_opens() {
+        let settings = settings(Some(window("09:00", "19:00")));
+
+        // Reminded at 18:45 in Paris, the next one is 09:00 the day after and not 19:45
+        let due = next_reminder_time(&settings, utc("2025-06-02 16:45"));
+        assert_eq!(due, Some(utc("2025-06-03 07:00")));
+        assert_eq!(
+            next_reminder_time(&settings, due.unwrap()),
+            Some(utc("2025-06-03 08:00"))
+        );
+    }
+
+    fn cron_settings(expression: &str) -> UserSettings {
+        UserSettings {
+            cron: CronSchedule::from_db(expression),
+            ..settings(None)
+        }
+    }
+
+    #[test]
+    fn cron_expressions_are_validated() {
+        let now = utc("2025-06-02 06:00");
+        let paris = Tz::Europe__Paris;
+
+        let cron = CronSchedule::parse(" 0 */45 9-18 * * MON-FRI ", paris, now).unwrap();
+        assert_eq!(cron.expression(), "0 */45 9-18 * * MON-FRI");
+
+        let too_frequent = CronSchedule::parse("0 */5 * * * *", paris, now).unwrap_err();
+        assert!(too_frequent.contains("trop souvent"), "{too_frequent}");
+        let never = CronSchedule::parse("0 0 12 30 2 *", paris, now).unwrap_err();
+        assert!(never.contains("ne se déclenche jamais"), "{never}");
+        let five_fields = CronSchedule::parse("*/45 9-18 * * MON-FRI", paris, now).unwrap_err();
+        assert!(five_fields.contains("6 champs"), "{five_fields}");
+    }
+
+    #[test]
+    fn cron_gaps_may_be_the_minimum_but_not_shorter() {
+        let now = utc("2025-06-02 06:00");
+        assert!(CronSchedule::parse("0 */10 * * * *", Tz::UTC, now).is_ok());
+        assert!(CronSchedule::parse("0 0,9 * * * *", Tz::UTC, now).is_err());
+    }
+
+    #[test]
+    fn cron_schedules_fire_in_the_user_time_zone() {
+        let settings = cron_settings("0 */45 9-18 * * MON-FRI");
+
+        // Monday 09:10 in Paris, then 09:45 and 10:00
+        assert_eq!(
+            next_active_time(&settings, utc("2025-06-02 07:10")),
+            Some(utc("2025-06-02 07:45"))
+        );
+        assert_eq!(
+            next_reminder_time(&settings, utc("2025-06-02 07:45")),
+            Some(utc("2025-06-02 08:00"))
+        );
+        // A fire time is itself active
+        assert_eq!(
+            next_active_time(&settings, utc("2025-06-02 07:45")),
+            Some(utc("2025-06-02 07:45"))
+        );
+        // Friday 18:46, the next one is on Monday morning
+        assert_eq!(
+            next_active_time(&settings, utc("2025-06-06 16:46")),
+            Some(utc("2025-06-09 07:00"))
+        );
+    }
+
+    #[test]
+    fn cron_schedules_replace_the_window_and_the_day_profiles() {
+        let mut settings = cron_settings("0 0 22 * * *");
+        settings.active_window = Some(window("09:00", "19:00"));
+        settings.profiles.set(Weekday::Mon, Some(DayProfile::Off));
+
+        assert_eq!(
+            next_active_time(&settings, utc("2025-06-02 12:00")),
+            Some(utc("2025-06-02 20:00"))
+        );
+    }
+
+    #[test]
+    fn dates_without_a_year_are_the_next_ones() {
+        let today = date("2025-12-20");
+        assert_eq!(parse_date("24/12", today), Some(date("2025-12-24")));
+        assert_eq!(parse_date("20/12", today), Some(today));
+        assert_eq!(parse_date("05/01", today), Some(date("2026-01-05")));
+        assert_eq!(parse_date("24/12/2027", today), Some(date("2027-12-24")));
+        assert_eq!(parse_date("2027-12-24", today), Some(date("2027-12-24")));
+        assert_eq!(parse_date("31/02", today), None);
+        assert_eq!(parse_date("demain", today), None);
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("45m"), Some(TimeDelta::minutes(45)));
+        assert_eq!(parse_duration("45 min"), Some(TimeDelta::minutes(45)));
+        assert_eq!(parse_duration("2h"), Some(TimeDelta::hours(2)));
+        assert_eq!(parse_duration("1h30"), Some(TimeDelta::minutes(90)));
+        assert_eq!(parse_duration("3J"), Some(TimeDelta::days(3)));
+        assert_eq!(parse_duration("90"), Some(TimeDelta::minutes(90)));
+        assert_eq!(parse_duration("0"), None);
+        assert_eq!(parse_duration("0h"), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("2 semaines"), None);
+        assert_eq!(parse_duration("30s"), None);
+    }
+
+    #[test]
+    fn durations_out_of_range_are_rejected() {
+        assert_eq!(parse_duration("999999999999999"), None);
+        assert_eq!(parse_duration("999999999999999h"), None);
+        assert_eq!(parse_duration("99999999999999999999"), None);
+    }
+}
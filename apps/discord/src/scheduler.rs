@@ -0,0 +1,283 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use chrono::NaiveDateTime;
+use serenity::all::{User, UserId};
+use tokio::sync::Notify;
+
+use crate::clock::Clock;
+
+/// Stale heap entries are dropped once they outnumber the live ones by this factor
+const QUEUE_COMPACTION_FACTOR: usize = 2;
+
+pub static SCHEDULER: LazyLock<Scheduler> = LazyLock::new(Scheduler::default);
+
+/// Something to send once its deadline is reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Job {
+    /// The next recurring reminder of a user
+    Reminder(UserId),
+    /// A one-off reminder, by its database id
+    OneOff(i64),
+    /// The next check of a user's voice session
+    VoiceNudge(UserId),
+    /// The message welcoming a user back once its absence is over
+    WelcomeBack(UserId),
+}
+
+struct ScheduledJob {
+    user: User,
+    generation: u64,
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    /// Min-heap of deadlines, an entry is stale once its generation isn't the job's one anymore
+    queue: BinaryHeap<Reverse<(NaiveDateTime, Job, u64)>>,
+    scheduled: HashMap<Job, ScheduledJob>,
+    generation: u64,
+}
+
+impl SchedulerState {
+    fn is_live(&self, job: &Job, generation: u64) -> bool {
+        self.scheduled
+            .get(job)
+            .is_some_and(|scheduled| scheduled.generation == generation)
+    }
+
+    /// Drops the stale entries sitting at the top of the queue, then peeks the earliest deadline
+    fn next_due(&mut self) -> Option<NaiveDateTime> {
+        while let Some(Reverse((due, job, generation))) = self.queue.peek() {
+            if self.is_live(job, *generation) {
+                return Some(*due);
+            }
+            self.queue.pop();
+        }
+        None
+    }
+
+    fn compact(&mut self) {
+        if self.queue.len() <= QUEUE_COMPACTION_FACTOR * self.scheduled.len() + 64 {
+            return;
+        }
+        let queue = std::mem::take(&mut self.queue);
+        self.queue = queue
+            .into_iter()
+            .filter(|Reverse((_, job, generation))| self.is_live(job, *generation))
+            .collect();
+    }
+}
+
+/// Keeps every registered user keyed on its next reminder time, along with one-off reminders, so
+/// the reminder loop only wakes up when a reminder is actually due
+#[derive(Default)]
+pub struct Scheduler {
+    state: Mutex<SchedulerState>,
+    wake: Notify,
+}
+
+impl Scheduler {
+    /// Replaces the deadline of a job, None removes the job from the queue
+    pub fn schedule_job(&self, job: Job, user: &User, due: Option<NaiveDateTime>) {
+        {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            match due {
+                Some(due) => {
+                    state.generation += 1;
+                    let generation = state.generation;
+                    state.scheduled.insert(
+                        job,
+                        ScheduledJob {
+                            user: user.clone(),
+                            generation,
+                        },
+                    );
+                    state.queue.push(Reverse((due, job, generation)));
+                    state.compact();
+                }
+                None => {
+                    state.scheduled.remove(&job);
+                }
+            }
+        }
+        // The earliest deadline may have changed
+        self.wake.notify_one();
+    }
+
+    /// Replaces the deadline of the recurring reminders of a user
+    pub fn schedule(&self, user: &User, due: Option<NaiveDateTime>) {
+        self.schedule_job(Job::Reminder(user.id), user, due);
+    }
+
+    pub fn unschedule(&self, user: &User) {
+        self.schedule(user, None);
+    }
+
+    pub fn next_due(&self) -> Option<NaiveDateTime> {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .next_due()
+    }
+
+    /// Removes and returns every job whose deadline is at or before `now`, with its user
+    pub fn pop_due(&self, now: NaiveDateTime) -> Vec<(Job, User)> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let mut due_jobs = Vec::new();
+        while let Some(due) = state.next_due() {
+            if due > now {
+                break;
+            }
+            if let Some(Reverse((_, job, _))) = state.queue.pop()
+                && let Some(scheduled) = state.scheduled.remove(&job)
+            {
+                due_jobs.push((job, scheduled.user));
+            }
+        }
+        due_jobs
+    }
+
+    /// Sleeps until the earliest deadline, or until the queue changes
+    pub async fn wait_for_next_due(&self, clock: &dyn Clock) {
+        let Some(due) = self.next_due() else {
+            self.wake.notified().await;
+            return;
+        };
+
+        let delay = (due - clock.now()).to_std().unwrap_or(Duration::ZERO);
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = self.wake.notified() => {}
+        }
+    }
+
+    /// Drops every job, as a restart of the bot would
+    #[cfg(test)]
+    pub fn clear(&self) {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner()) = SchedulerState::default();
+    }
+
+    pub fn scheduled_count(&self) -> usize {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .scheduled
+            .len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    fn time(time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn user(id: u64) -> User {
+        let mut user = User::default();
+        user.id = UserId::new(id);
+        user
+    }
+
+    fn queue_len(scheduler: &Scheduler) -> usize {
+        scheduler.state.lock().unwrap().queue.len()
+    }
+
+    #[test]
+    fn rescheduled_users_only_keep_their_latest_deadline() {
+        let scheduler = Scheduler::default();
+        let alice = user(1);
+        scheduler.schedule(&alice, Some(time("2026-03-02 09:00")));
+        scheduler.schedule(&alice, Some(time("2026-03-02 10:00")));
+
+        // The 09:00 entry is still in the heap, but stale
+        assert_eq!(queue_len(&scheduler), 2);
+        assert_eq!(scheduler.next_due(), Some(time("2026-03-02 10:00")));
+        assert!(scheduler.pop_due(time("2026-03-02 09:30")).is_empty());
+        assert_eq!(
+            scheduler.pop_due(time("2026-03-02 10:00")),
+            vec![(Job::Reminder(alice.id), alice)]
+        );
+        assert_eq!(scheduler.next_due(), None);
+    }
+
+    #[test]
+    fn unscheduled_users_are_skipped() {
+        let scheduler = Scheduler::default();
+        let (alice, bob) = (user(1), user(2));
+        scheduler.schedule(&alice, Some(time("2026-03-02 09:00")));
+        scheduler.schedule(&bob, Some(time("2026-03-02 09:30")));
+        scheduler.unschedule(&alice);
+
+        assert_eq!(scheduler.next_due(), Some(time("2026-03-02 09:30")));
+        assert_eq!(
+            scheduler.pop_due(time("2026-03-02 12:00")),
+            vec![(Job::Reminder(bob.id), bob)]
+        );
+        assert_eq!(scheduler.scheduled_count(), 0);
+    }
+
+    #[test]
+    fn jobs_of_every_kind_pop_in_due_order() {
+        let scheduler = Scheduler::default();
+        let (alice, bob) = (user(1), user(2));
+        scheduler.schedule_job(
+            Job::VoiceNudge(bob.id),
+            &bob,
+            Some(time("2026-03-02 09:20")),
+        );
+        scheduler.schedule_job(Job::OneOff(7), &alice, Some(time("2026-03-02 09:10")));
+        scheduler.schedule(&alice, Some(time("2026-03-02 09:30")));
+        scheduler.schedule_job(Job::OneOff(3), &bob, Some(time("2026-03-02 09:00")));
+
+        let jobs: Vec<Job> = scheduler
+            .pop_due(time("2026-03-02 09:20"))
+            .into_iter()
+            .map(|(job, _)| job)
+            .collect();
+        assert_eq!(
+            jobs,
+            vec![Job::OneOff(3), Job::OneOff(7), Job::VoiceNudge(bob.id)]
+        );
+        assert_eq!(scheduler.next_due(), Some(time("2026-03-02 09:30")));
+    }
+
+    #[test]
+    fn compaction_keeps_only_the_live_entries() {
+        let scheduler = Scheduler::default();
+        let (alice, bob) = (user(1), user(2));
+        scheduler.schedule(&bob, Some(time("2026-03-03 09:00")));
+        let start = time("2026-03-02 09:00");
+        for minutes in 0..200 {
+            scheduler.schedule(&alice, Some(start + TimeDelta::minutes(minutes)));
+        }
+        // Stale entries never pile up past the threshold
+        assert!(queue_len(&scheduler) <= QUEUE_COMPACTION_FACTOR * 2 + 64 + 1);
+
+        let mut state = scheduler.state.lock().unwrap();
+        let stale =
+            (0..100).map(|generation| Reverse((start, Job::Reminder(alice.id), generation)));
+        state.queue.extend(stale);
+        state.compact();
+        let mut live: Vec<_> = state.queue.iter().map(|Reverse(entry)| *entry).collect();
+        live.sort();
+        assert_eq!(
+            live,
+            vec![
+                (
+                    start + TimeDelta::minutes(199),
+                    Job::Reminder(alice.id),
+                    201
+                ),
+                (time("2026-03-03 09:00"), Job::Reminder(bob.id), 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1029 @@
+//! Deterministic scheduling tests: the reminder loop runs on a virtual clock that jumps from one
+//! deadline to the next, and deliveries are recorded instead of being sent to Discord
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{NaiveDateTime, NaiveTime, TimeDelta};
+use chrono_tz::Tz;
+use serenity::{
+    Error as SerenityError,
+    all::{ChannelId, CreateMessage, GuildId, OnlineStatus, User, UserId},
+};
+use tokio::{
+    sync::{MutexGuard, oneshot, watch},
+    task::JoinSet,
+    time::Instant,
+};
+
+use crate::{
+    adaptive::AdaptiveBounds,
+    catch_up::{CatchUpPolicy, apply_catch_up_policy},
+    clock::Clock,
+    database::{self, UserData},
+    delivery::DeliveryTarget,
+    goal::DailyGoal,
+    one_off::{ONE_OFF_REMINDERS, add_one_off_reminder},
+    presence::{PRESENCES, PresenceEvent, PresenceTracker, presence_return_delay},
+    registry::{
+        PauseState, ReminderFrequency, UserSettings, acknowledge_user_reminder, add_user_absence,
+        clear_registry, insert_new_user_to_remind, is_user_suspended, lookup_last_reminded,
+        lookup_user_absences, pause_user_reminders, record_user_intake, release_deferred_reminder,
+        restore_user, snooze_user_reminder, update_user_goal,
+    },
+    reminder::{Courier, dispatch_due_jobs, run_reminders},
+    schedule::{Absence, ActiveWindow},
+    scheduler::SCHEDULER,
+    voice::{
+        clear_voice_states, lookup_voice_session, sync_guild_voice_states, update_voice_state,
+    },
+};
+
+struct VirtualClock {
+    now: Mutex<NaiveDateTime>,
+    /// When set, the clock also moves along with tokio's time from that instant, so that the
+    /// retry delays of a test with paused time show
+    ticking_since: Mutex<Option<Instant>>,
+}
+
+impl VirtualClock {
+    fn set(&self, now: NaiveDateTime) {
+        *self.now.lock().unwrap() = now;
+        if let Some(since) = &mut *self.ticking_since.lock().unwrap() {
+            *since = Instant::now();
+        }
+    }
+
+    /// Lets the clock move with tokio's time, which only the test controls once paused
+    fn follow_tokio_time(&self) {
+        *self.ticking_since.lock().unwrap() = Some(Instant::now());
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> NaiveDateTime {
+        let now = *self.now.lock().unwrap();
+        match *self.ticking_since.lock().unwrap() {
+            Some(since) => now + TimeDelta::from_std(since.elapsed()).unwrap(),
+            None => now,
+        }
+    }
+}
+
+/// A reminder handed to the courier: who got it, when, and what it said
+#[derive(Debug, Clone)]
+struct Delivery {
+    user: String,
+    at: NaiveDateTime,
+    content: String,
+}
+
+type ErrorKind = fn() -> SerenityError;
+
+struct RecordingCourier {
+    clock: Arc<VirtualClock>,
+    deliveries: Mutex<Vec<Delivery>>,
+    /// Fired by the next delivery, while it is still in flight
+    on_delivery: Mutex<Option<oneshot::Sender<()>>>,
+    /// When set, that many deliveries fail with this error instead of being recorded
+    failure: Mutex<Option<(ErrorKind, u32)>>,
+    /// Users told in the guild that their reminders got suspended
+    suspension_notices: Mutex<Vec<String>>,
+}
+
+impl RecordingCourier {
+    /// Makes the next `times` deliveries fail with `error`
+    fn fail(&self, times: u32, error: ErrorKind) {
+        *self.failure.lock().unwrap() = Some((error, times));
+    }
+}
+
+impl Courier for RecordingCourier {
+    async fn send_reminder(
+        &self,
+        user: &User,
+        _target: DeliveryTarget,
+        content: &str,
+        _message: CreateMessage,
+    ) -> Result<(), SerenityError> {
+        if let Some(on_delivery) = self.on_delivery.lock().unwrap().take() {
+            on_delivery.send(()).unwrap();
+        }
+        tokio::task::yield_now().await;
+        if let Some((error, times)) = &mut *self.failure.lock().unwrap()
+            && *times > 0
+        {
+            *times -= 1;
+            return Err(error());
+        }
+        self.deliveries.lock().unwrap().push(Delivery {
+            user: user.name.clone(),
+            at: self.clock.now(),
+            content: content.to_string(),
+        });
+        Ok(())
+    }
+
+    async fn notify_suspension(&self, user: &User) {
+        self.suspension_notices
+            .lock()
+            .unwrap()
+            .push(user.name.clone());
+    }
+}
+
+struct Simulation {
+    clock: Arc<VirtualClock>,
+    courier: Arc<RecordingCourier>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Simulation {
+    /// Starts from an empty registry and database, at `at`
+    async fn start(at: &str) -> Self {
+        let lock = database::init_memory_database().await;
+        clear_registry().await;
+        ONE_OFF_REMINDERS.write().await.clear();
+        *PRESENCES.lock().unwrap() = PresenceTracker::default();
+        clear_voice_states().await;
+        SCHEDULER.clear();
+
+        let clock = Arc::new(VirtualClock {
+            now: Mutex::new(time(at)),
+            ticking_since: Mutex::new(None),
+        });
+        let courier = Arc::new(RecordingCourier {
+            clock: Arc::clone(&clock),
+            deliveries: Mutex::new(Vec::new()),
+            on_delivery: Mutex::new(None),
+            failure: Mutex::new(None),
+            suspension_notices: Mutex::new(Vec::new()),
+        });
+        Self {
+            clock,
+            courier,
+            _lock: lock,
+        }
+    }
+
+    /// Registers a user now, as `/register` does
+    async fn register(&self, user: &User, settings: UserSettings) {
+        insert_new_user_to_remind(user, settings, self.clock.now()).await;
+    }
+
+    /// Applies a change of status now, releasing the reminders of the users coming back as the
+    /// bot does
+    async fn presence(&self, event: PresenceEvent) {
+        let returned = PRESENCES.lock().unwrap().apply(event);
+        for user in returned {
+            release_deferred_reminder(&user, self.clock.now() + presence_return_delay()).await;
+        }
+    }
+
+    /// Moves a member to a voice channel of `GUILD`, or out of voice, now
+    async fn voice(&self, user: &User, channel_id: Option<ChannelId>) {
+        update_voice_state(user.id, Some(GUILD), channel_id, self.clock.now()).await;
+    }
+
+    /// Runs the reminder loop up to `until` included, and returns the deliveries made meanwhile
+    async fn run_until(&self, until: &str) -> Vec<Delivery> {
+        let until = time(until);
+        let clock: Arc<dyn Clock> = self.clock.clone();
+        let (_running, stopping) = watch::channel(false);
+        while let Some(due) = SCHEDULER.next_due()
+            && due <= until
+        {
+            self.clock.set(due.max(self.clock.now()));
+            let mut deliveries = JoinSet::new();
+            dispatch_due_jobs(
+                &mut deliveries,
+                &self.courier,
+                &clock,
+                &stopping,
+                self.clock.now(),
+            );
+            while let Some(res) = deliveries.join_next().await {
+                res.unwrap();
+            }
+        }
+        self.clock.set(until);
+
+        let mut deliveries = std::mem::take(&mut *self.courier.deliveries.lock().unwrap());
+        deliveries.sort_by(|a, b| (a.at, &a.user).cmp(&(b.at, &b.user)));
+        deliveries
+    }
+
+    /// Like `run_until`, only keeping who was reminded when
+    async fn reminders_until(&self, until: &str) -> Vec<(String, NaiveDateTime)> {
+        self.run_until(until)
+            .await
+            .into_iter()
+            .map(|delivery| (delivery.user, delivery.at))
+            .collect()
+    }
+
+    /// Forgets everything kept in memory, then starts again at `at` from the database, as the
+    /// bot does on startup
+    async fn restart(&self, at: &str, policy: CatchUpPolicy) {
+        clear_registry().await;
+        SCHEDULER.clear();
+        self.clock.set(time(at));
+
+        let now = self.clock.now();
+        let stored_users = database::load_user_reminders(now).await.unwrap();
+        for (UserData { id, name }, stored_user) in stored_users.iter() {
+            restore_user(&user(*id, name), stored_user).await;
+        }
+        apply_catch_up_policy(policy, now).await;
+    }
+}
+
+const GUILD: GuildId = GuildId::new(1);
+const LOUNGE: ChannelId = ChannelId::new(10);
+const AFK: ChannelId = ChannelId::new(11);
+
+fn time(time: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap()
+}
+
+fn user(id: u64, name: &str) -> User {
+    let mut user = User::default();
+    user.id = UserId::new(id);
+    user.name = name.to_string();
+    user
+}
+
+fn reminded(name: &str, at: &str) -> (String, NaiveDateTime) {
+    (name.to_string(), time(at))
+}
+
+fn connection_reset() -> SerenityError {
+    SerenityError::Io(std::io::Error::other("connection reset"))
+}
+
+fn settings(frequency: ReminderFrequency) -> UserSettings {
+    UserSettings::new(frequency, Tz::UTC)
+}
+
+fn window(start: &str, end: &str) -> Option<ActiveWindow> {
+    let start = NaiveTime::parse_from_str(start, "%H:%M").unwrap();
+    let end = NaiveTime::parse_from_str(end, "%H:%M").unwrap();
+    ActiveWindow::new(start, end)
+}
+
+#[tokio::test]
+async fn users_are_reminded_at_their_own_interval() {
+    let sim = Simulation::start("2026-03-02 08:00").await;
+    let (alice, bob) = (user(1, "alice"), user(2, "bob"));
+    sim.register(&alice, settings(ReminderFrequency::ThirtyMin))
+        .await;
+    sim.register(&bob, settings(ReminderFrequency::OneHour))
+        .await;
+
+    assert_eq!(
+        sim.reminders_until("2026-03-02 10:00").await,
+        vec![
+            reminded("alice", "2026-03-02 08:30"),
+            reminded("alice", "2026-03-02 09:00"),
+            reminded("bob", "2026-03-02 09:00"),
+            reminded("alice", "2026-03-02 09:30"),
+            reminded("alice", "2026-03-02 10:00"),
+            reminded("bob", "2026-03-02 10:00"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn reminders_only_fall_inside_the_active_window() {
+    let sim = Simulation::start("2026-03-02 15:00").await;
+    let alice = user(1, "alice");
+    let mut alice_settings = settings(ReminderFrequency::OneHour);
+    alice_settings.active_window = window("09:00", "17:00");
+    sim.register(&alice, alice_settings).await;
+
+    // The window end is excluded, the reminder due at 17:00 moves to the next opening
+    assert_eq!(
+        sim.reminders_until("2026-03-03 10:00").await,
+        vec![
+            reminded("alice", "2026-03-02 16:00"),
+            reminded("alice", "2026-03-03 09:00"),
+            reminded("alice", "2026-03-03 10:00"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn windows_spanning_midnight_keep_reminding_past_it() {
+    let sim = Simulation::start("2026-03-02 21:30").await;
+    let alice = user(1, "alice");
+    let mut alice_settings = settings(ReminderFrequency::OneHour);
+    alice_settings.active_window = window("22:00", "02:00");
+    sim.register(&alice, alice_settings).await;
+
+    assert_eq!(
+        sim.reminders_until("2026-03-03 22:30").await,
+        vec![
+            reminded("alice", "2026-03-02 22:30"),
+            reminded("alice", "2026-03-02 23:30"),
+            reminded("alice", "2026-03-03 00:30"),
+            reminded("alice", "2026-03-03 01:30"),
+            reminded("alice", "2026-03-03 22:00"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn snoozes_ending_in_quiet_hours_wait_for_the_window() {
+    let sim = Simulation::start("2026-03-02 17:30").await;
+    let alice = user(1, "alice");
+    let mut alice_settings = settings(ReminderFrequency::OneHour);
+    alice_settings.active_window = window("09:00", "19:00");
+    sim.register(&alice, alice_settings).await;
+    assert_eq!(
+        sim.reminders_until("2026-03-02 18:50").await,
+        vec![reminded("alice", "2026-03-02 18:30")]
+    );
+
+    // Snoozed for 30 minutes at 18:50, past the end of the window
+    snooze_user_reminder(&alice, time("2026-03-02 19:20")).await;
+    assert_eq!(
+        sim.reminders_until("2026-03-03 09:30").await,
+        vec![reminded("alice", "2026-03-03 09:00")]
+    );
+}
+
+#[tokio::test]
+async fn reminders_due_at_the_end_of_a_run_are_sent() {
+    let sim = Simulation::start("2026-03-02 08:00").await;
+    let alice = user(1, "alice");
+    sim.register(&alice, settings(ReminderFrequency::OneHour))
+        .await;
+
+    assert!(sim.reminders_until("2026-03-02 08:59").await.is_empty());
+    assert_eq!(
+        sim.reminders_until("2026-03-02 09:00").await,
+        vec![reminded("alice", "2026-03-02 09:00")]
+    );
+}
+
+#[tokio::test]
+async fn registering_again_changes_the_frequency_from_now() {
+    let sim = Simulation::start("2026-03-02 08:00").await;
+    let (alice, bob) = (user(1, "alice"), user(2, "bob"));
+    sim.register(&alice, settings(ReminderFrequency::OneHour))
+        .await;
+    sim.register(&bob, settings(ReminderFrequency::OneHour))
+        .await;
+
+    assert_eq!(
+        sim.reminders_until("2026-03-02 09:15").await,
+        vec![
+            reminded("alice", "2026-03-02 09:00"),
+            reminded("bob", "2026-03-02 09:00"),
+        ]
+    );
+
+    sim.register(&alice, settings(ReminderFrequency::ThreeHours))
+        .await;
+    assert_eq!(
+        sim.reminders_until("2026-03-02 12:15").await,
+        vec![
+            reminded("bob", "2026-03-02 10:00"),
+            reminded("bob", "2026-03-02 11:00"),
+            reminded("bob", "2026-03-02 12:00"),
+            reminded("alice", "2026-03-02 12:15"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn adaptive_intervals_follow_the_acknowledgements() {
+    let sim = Simulation::start("2026-03-02 08:00").await;
+    let alice = user(1, "alice");
+    let mut alice_settings = settings(ReminderFrequency::OneHour);
+    alice_settings.adaptive = AdaptiveBounds::new(30, 120);
+    sim.register(&alice, alice_settings).await;
+    assert_eq!(
+        sim.reminders_until("2026-03-02 09:00").await,
+        vec![reminded("alice", "2026-03-02 09:00")]
+    );
+
+    // Drinking within 10 minutes grows the interval to 72 minutes
+    acknowledge_user_reminder(&alice, time("2026-03-02 09:05"), true).await;
+    assert_eq!(
+        sim.reminders_until("2026-03-02 10:12").await,
+        vec![reminded("alice", "2026-03-02 10:12")]
+    );
+
+    // Drinking later leaves it as is
+    acknowledge_user_reminder(&alice, time("2026-03-02 10:30"), true).await;
+    assert_eq!(
+        sim.reminders_until("2026-03-02 11:24").await,
+        vec![reminded("alice", "2026-03-02 11:24")]
+    );
+
+    // Ignoring that reminder shrinks it when the next one is sent
+    assert_eq!(
+        sim.reminders_until("2026-03-02 13:34").await,
+        vec![
+            reminded("alice", "2026-03-02 12:36"),
+            reminded("alice", "2026-03-02 13:34"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn daily_goals_are_rebalanced_on_the_intake() {
+    let sim = Simulation::start("2026-03-02 08:00").await;
+    let alice = user(1, "alice");
+    let mut alice_settings = settings(ReminderFrequency::OneHour);
+    alice_settings.active_window = window("09:00", "21:00");
+    sim.register(&alice, alice_settings).await;
+    // 2 l over 12 hours is a glass every 90 minutes
+    update_user_goal(&alice, DailyGoal::new(2000))
+        .await
+        .unwrap();
+    assert_eq!(
+        sim.reminders_until("2026-03-02 09:30").await,
+        vec![reminded("alice", "2026-03-02 09:30")]
+    );
+
+    // Half of the goal drunk early, the 4 glasses left are spread over the 11 hours left
+    record_user_intake(&alice, time("2026-03-02 10:00"), Some(1000)).await;
+    assert_eq!(
+        sim.reminders_until("2026-03-02 14:30").await,
+        vec![
+            reminded("alice", "2026-03-02 12:15"),
+            reminded("alice", "2026-03-02 14:26"),
+        ]
+    );
+
+    // Then nothing more is drunk, reminders come closer again
+    assert_eq!(
+        sim.reminders_until("2026-03-02 18:00").await,
+        vec![
+            reminded("alice", "2026-03-02 16:04"),
+            reminded("alice", "2026-03-02 17:18"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn quick_restarts_keep_the_schedule() {
+    let sim = Simulation::start("2026-03-02 08:00").await;
+    let alice = user(1, "alice");
+    sim.register(&alice, settings(ReminderFrequency::OneHour))
+        .await;
+    assert_eq!(
+        sim.reminders_until("2026-03-02 09:10").await,
+        vec![reminded("alice", "2026-03-02 09:00")]
+    );
+
+    sim.restart("2026-03-02 09:20", CatchUpPolicy::Skip).await;
+    assert_eq!(
+        sim.reminders_until("2026-03-02 11:00").await,
+        vec![
+            reminded("alice", "2026-03-02 10:00"),
+            reminded("alice", "2026-03-02 11:00"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn reminders_missed_while_offline_are_skipped() {
+    let sim = Simulation::start("2026-03-02 08:00").await;
+    let (alice, bob) = (user(1, "alice"), user(2, "bob"));
+    sim.register(&alice, settings(ReminderFrequency::OneHour))
+        .await;
+    sim.register(&bob, settings(ReminderFrequency::ThreeHours))
+        .await;
+    assert_eq!(
+        sim.reminders_until("2026-03-02 09:00").await,
+        vec![reminded("alice", "2026-03-02 09:00")]
+    );
+
+    // Offline from 09:30 to 12:40, bob's reminder at 11:00 and alice's ones are missed
+    sim.restart("2026-03-02 12:40", CatchUpPolicy::Skip).await;
+    assert_eq!(
+        sim.reminders_until("2026-03-02 15:40").await,
+        vec![
+            reminded("alice", "2026-03-02 13:40"),
+            reminded("alice", "2026-03-02 14:40"),
+            reminded("alice", "2026-03-02 15:40"),
+            reminded("bob", "2026-03-02 15:40"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn summary_policy_reminds_right_away_with_the_missed_count() {
+    let sim = Simulation::start("2026-03-02 08:00").await;
+    let alice = user(1, "alice");
+    sim.register(&alice, settings(ReminderFrequency::OneHour))
+        .await;
+
+    // Reminders of 09:00, 10:00 and 11:00 are missed
+    sim.restart("2026-03-02 11:30", CatchUpPolicy::Summary)
+        .await;
+    let deliveries = sim.run_until("2026-03-02 12:30").await;
+    let reminders: Vec<_> = deliveries
+        .iter()
+        .map(|delivery| (delivery.user.clone(), delivery.at))
+        .collect();
+    assert_eq!(
+        reminders,
+        vec![
+            reminded("alice", "2026-03-02 11:30"),
+            reminded("alice", "2026-03-02 12:30"),
+        ]
+    );
+    assert!(deliveries[0].content.contains("tu as manqué 3 rappels"));
+    assert!(!deliveries[1].content.contains("manqué"));
+}
+
+#[tokio::test]
+async fn summary_after_a_restart_in_quiet_hours_waits_for_the_window() {
+    let sim = Simulation::start("2026-03-02 17:30").await;
+    let alice = user(1, "alice");
+    let mut alice_settings = settings(ReminderFrequency::OneHour);
+    alice_settings.active_window = window("09:00", "19:00");
+    sim.register(&alice, alice_settings).await;
+    assert_eq!(
+        sim.reminders_until("2026-03-02 18:30").await,
+        vec![reminded("alice", "2026-03-02 18:30")]
+    );
+
+    // Offline all day long, the 10 reminders from 09:00 to 18:00 are missed
+    sim.restart("2026-03-04 03:00", CatchUpPolicy::Summary)
+        .await;
+    let deliveries = sim.run_until("2026-03-04 10:00").await;
+    let reminders: Vec<_> = deliveries
+        .iter()
+        .map(|delivery| (delivery.user.clone(), delivery.at))
+        .collect();
+    assert_eq!(
+        reminders,
+        vec![
+            reminded("alice", "2026-03-04 09:00"),
+            reminded("alice", "2026-03-04 10:00"),
+        ]
+    );
+    assert!(deliveries[0].content.contains("tu as manqué 10 rappels"));
+}
+
+#[tokio::test]
+async fn spread_after_a_restart_in_quiet_hours_starts_when_the_window_opens() {
+    let sim = Simulation::start("2026-03-02 17:30").await;
+    let (alice, bob) = (user(1, "alice"), user(2, "bob"));
+    for user in [&alice, &bob] {
+        let mut user_settings = settings(ReminderFrequency::OneHour);
+        user_settings.active_window = window("09:00", "19:00");
+        sim.register(user, user_settings).await;
+    }
+    assert_eq!(sim.reminders_until("2026-03-02 18:30").await.len(), 2);
+
+    sim.restart("2026-03-04 03:00", CatchUpPolicy::Spread).await;
+    // Both were due at the same time, either may come first
+    let reminders = sim.reminders_until("2026-03-04 09:10").await;
+    let times: Vec<_> = reminders.iter().map(|(_, at)| *at).collect();
+    assert_eq!(
+        times,
+        vec![
+            time("2026-03-04 09:00"),
+            time("2026-03-04 09:02") + TimeDelta::seconds(30),
+        ]
+    );
+    assert_ne!(reminders[0].0, reminders[1].0);
+}
+
+#[tokio::test]
+async fn shutting_down_records_the_reminders_in_flight() {
+    let sim = Simulation::start("2026-03-02 08:00").await;
+    let alice = user(1, "alice");
+    sim.register(&alice, settings(ReminderFrequency::OneHour))
+        .await;
+
+    // The shutdown is requested while the reminder of 09:00 is being sent
+    sim.clock.set(time("2026-03-02 09:00"));
+    let (stop, stopping) = oneshot::channel();
+    *sim.courier.on_delivery.lock().unwrap() = Some(stop);
+    run_reminders(Arc::clone(&sim.courier), sim.clock.clone(), async {
+        stopping.await.ok();
+    })
+    .await;
+
+    // Once restarted, the reminder isn't sent a second time
+    sim.restart("2026-03-02 09:05", CatchUpPolicy::Skip).await;
+    assert_eq!(
+        sim.reminders_until("2026-03-02 10:00").await,
+        vec![
+            reminded("alice", "2026-03-02 09:00"),
+            reminded("alice", "2026-03-02 10:00"),
+        ]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn transient_failures_are_retried_with_a_growing_delay() {
+    let sim = Simulation::start("2026-03-02 08:00").await;
+    sim.clock.follow_tokio_time();
+    let alice = user(1, "alice");
+    sim.register(&alice, settings(ReminderFrequency::OneHour))
+        .await;
+    let at = |time: NaiveDateTime, seconds| time + TimeDelta::seconds(seconds);
+
+    // Two failures, retried 2 then 4 seconds later
+    sim.courier.fail(2, connection_reset);
+    let nine = time("2026-03-02 09:00");
+    assert_eq!(
+        sim.reminders_until("2026-03-02 09:30").await,
+        vec![("alice".to_string(), at(nine, 6))]
+    );
+    assert_eq!(lookup_last_reminded(&alice).await, Some(at(nine, 6)));
+    assert_eq!(
+        database::load_delivery_attempts(&alice).await.unwrap(),
+        vec![
+            (nine, 1, "TransientFailure".to_string()),
+            (at(nine, 2), 2, "TransientFailure".to_string()),
+            (at(nine, 6), 3, "Delivered".to_string()),
+        ]
+    );
+
+    // Four failures give up on the reminder, which doesn't count as sent
+    sim.courier.fail(4, connection_reset);
+    let ten = at(time("2026-03-02 10:00"), 6);
+    assert!(sim.reminders_until("2026-03-02 10:30").await.is_empty());
+    assert_eq!(lookup_last_reminded(&alice).await, Some(at(nine, 6)));
+    let attempts = database::load_delivery_attempts(&alice).await.unwrap();
+    assert_eq!(
+        attempts[3..],
+        [
+            (ten, 1, "TransientFailure".to_string()),
+            (at(ten, 2), 2, "TransientFailure".to_string()),
+            (at(ten, 6), 3, "TransientFailure".to_string()),
+            (at(ten, 14), 4, "TransientFailure".to_string()),
+        ]
+    );
+    // The next one is still sent on time
+    assert_eq!(SCHEDULER.next_due(), Some(at(time("2026-03-02 11:00"), 6)));
+}
+
+#[tokio::test]
+async fn users_are_suspended_after_three_undelivered_reminders_in_a_row() {
+    let sim = Simulation::start("2026-03-02 08:00").await;
+    let alice = user(1, "alice");
+    sim.register(&alice, settings(ReminderFrequency::OneHour))
+        .await;
+    let closed_dms = || SerenityError::Other("Cannot send messages to this user");
+
+    // Two failures, then a delivery starting the count over
+    sim.courier.fail(2, closed_dms);
+    assert_eq!(
+        sim.reminders_until("2026-03-02 11:00").await,
+        vec![reminded("alice", "2026-03-02 11:00")]
+    );
+
+    // Two more failures aren't enough to suspend the user
+    sim.courier.fail(u32::MAX, closed_dms);
+    assert!(sim.reminders_until("2026-03-02 13:00").await.is_empty());
+    assert!(!is_user_suspended(&alice).await);
+    assert!(sim.courier.suspension_notices.lock().unwrap().is_empty());
+
+    // The third one in a row does, with a single notice and nothing scheduled anymore
+    assert!(sim.reminders_until("2026-03-03 13:00").await.is_empty());
+    assert!(is_user_suspended(&alice).await);
+    assert_eq!(
+        *sim.courier.suspension_notices.lock().unwrap(),
+        vec!["alice".to_string()]
+    );
+    assert_eq!(SCHEDULER.next_due(), None);
+
+    // The suspension outlives a restart
+    *sim.courier.failure.lock().unwrap() = None;
+    sim.restart("2026-03-03 14:00", CatchUpPolicy::Summary)
+        .await;
+    assert!(is_user_suspended(&alice).await);
+    assert!(sim.reminders_until("2026-03-04 14:00").await.is_empty());
+    assert_eq!(sim.courier.suspension_notices.lock().unwrap().len(), 1);
+}
+
+fn absence(start: &str, end: &str) -> Absence {
+    let date = |date| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+    Absence::new(date(start), date(end)).unwrap()
+}
+
+#[tokio::test]
+async fn absences_hold_reminders_back_then_welcome_the_user() {
+    let sim = Simulation::start("2026-03-02 15:30").await;
+    let alice = user(1, "alice");
+    let mut alice_settings = settings(ReminderFrequency::OneHour);
+    alice_settings.active_window = window("09:00", "18:00");
+    sim.register(&alice, alice_settings).await;
+    assert!(add_user_absence(&alice, absence("2026-03-03", "2026-03-04")).await);
+
+    // Nothing is sent during the absence, the user is welcomed back when the window opens
+    let deliveries = sim.run_until("2026-03-05 10:00").await;
+    let reminders: Vec<_> = deliveries
+        .iter()
+        .map(|delivery| (delivery.user.clone(), delivery.at))
+        .collect();
+    assert_eq!(
+        reminders,
+        vec![
+            reminded("alice", "2026-03-02 16:30"),
+            reminded("alice", "2026-03-02 17:30"),
+            reminded("alice", "2026-03-05 09:00"),
+            reminded("alice", "2026-03-05 09:00"),
+            reminded("alice", "2026-03-05 10:00"),
+        ]
+    );
+    let welcomes: Vec<_> = deliveries
+        .iter()
+        .filter(|delivery| delivery.content.contains("Bon retour de vacances"))
+        .map(|delivery| delivery.at)
+        .collect();
+    assert_eq!(welcomes, vec![time("2026-03-05 09:00")]);
+    assert!(lookup_user_absences(&alice).await.is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn absences_are_kept_until_the_welcome_back_is_delivered() {
+    let sim = Simulation::start("2026-03-02 20:00").await;
+    let alice = user(1, "alice");
+    sim.register(&alice, settings(ReminderFrequency::ThreeHours))
+        .await;
+    assert!(add_user_absence(&alice, absence("2026-03-02", "2026-03-03")).await);
+
+    // Discord is down when the absence ends, the welcome back is tried again 15 minutes later
+    sim.courier.fail(u32::MAX, connection_reset);
+    assert!(sim.run_until("2026-03-04 00:00").await.is_empty());
+    assert_eq!(lookup_user_absences(&alice).await.len(), 1);
+
+    *sim.courier.failure.lock().unwrap() = None;
+    let deliveries = sim.run_until("2026-03-04 03:00").await;
+    let sent: Vec<_> = deliveries
+        .iter()
+        .map(|delivery| {
+            (
+                delivery.at,
+                delivery.content.contains("Bon retour de vacances"),
+            )
+        })
+        .collect();
+    assert_eq!(
+        sent,
+        vec![
+            (time("2026-03-04 00:15"), true),
+            (time("2026-03-04 03:00"), false),
+        ]
+    );
+    assert!(lookup_user_absences(&alice).await.is_empty());
+}
+
+#[tokio::test]
+async fn one_off_reminders_stay_saved_until_delivered() {
+    let sim = Simulation::start("2026-03-02 08:00").await;
+    let alice = user(1, "alice");
+    add_one_off_reminder(&alice, time("2026-03-02 09:00"), None)
+        .await
+        .unwrap();
+
+    assert!(sim.reminders_until("2026-03-02 08:59").await.is_empty());
+    assert_eq!(database::load_one_off_reminders().await.unwrap().len(), 1);
+
+    assert_eq!(
+        sim.reminders_until("2026-03-02 09:00").await,
+        vec![reminded("alice", "2026-03-02 09:00")]
+    );
+    assert!(database::load_one_off_reminders().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn one_off_reminders_that_can_never_be_delivered_are_dropped() {
+    let sim = Simulation::start("2026-03-02 08:00").await;
+    let alice = user(1, "alice");
+    add_one_off_reminder(&alice, time("2026-03-02 09:00"), None)
+        .await
+        .unwrap();
+
+    // Like the DMs of the user being closed
+    sim.courier.fail(u32::MAX, || {
+        SerenityError::Other("Cannot send messages to this user")
+    });
+    assert!(sim.reminders_until("2026-03-02 12:00").await.is_empty());
+    assert!(database::load_one_off_reminders().await.unwrap().is_empty());
+    assert_eq!(SCHEDULER.next_due(), None);
+}
+
+#[tokio::test]
+async fn shutting_down_stops_retrying_and_keeps_the_reminder() {
+    let sim = Simulation::start("2026-03-02 08:00").await;
+    let alice = user(1, "alice");
+    add_one_off_reminder(&alice, time("2026-03-02 09:00"), None)
+        .await
+        .unwrap();
+
+    // Discord fails while the shutdown is requested, the retries would take 14 seconds
+    sim.courier.fail(u32::MAX, connection_reset);
+    sim.clock.set(time("2026-03-02 09:00"));
+    let (stop, stopping) = oneshot::channel();
+    *sim.courier.on_delivery.lock().unwrap() = Some(stop);
+    let stopped = tokio::time::timeout(
+        Duration::from_secs(1),
+        run_reminders(Arc::clone(&sim.courier), sim.clock.clone(), async {
+            stopping.await.ok();
+        }),
+    )
+    .await;
+    assert!(
+        stopped.is_ok(),
+        "the delivery kept retrying after the shutdown"
+    );
+
+    // Still saved, and tried again later
+    assert_eq!(database::load_one_off_reminders().await.unwrap().len(), 1);
+    assert_eq!(SCHEDULER.next_due(), Some(time("2026-03-02 09:15")));
+}
+
+#[tokio::test]
+async fn users_paused_while_deferred_wait_for_the_end_of_the_pause() {
+    let sim = Simulation::start("2026-03-02 08:00").await;
+    let alice = user(1, "alice");
+    let mut alice_settings = settings(ReminderFrequency::OneHour);
+    alice_settings.presence_aware = true;
+    sim.register(&alice, alice_settings).await;
+
+    // Offline when the reminder of 09:00 is due, it waits for her
+    sim.presence(PresenceEvent::Snapshot(Vec::new())).await;
+    assert!(sim.reminders_until("2026-03-02 09:10").await.is_empty());
+
+    pause_user_reminders(&alice, PauseState::Until(time("2026-03-02 12:00"))).await;
+    sim.clock.set(time("2026-03-02 09:30"));
+    sim.presence(PresenceEvent::Update(alice.id, OnlineStatus::Online))
+        .await;
+    assert_eq!(
+        sim.reminders_until("2026-03-02 12:30").await,
+        vec![reminded("alice", "2026-03-02 12:00")]
+    );
+}
+
+#[tokio::test]
+async fn users_back_online_in_quiet_hours_wait_for_the_window() {
+    let sim = Simulation::start("2026-03-02 17:30").await;
+    let alice = user(1, "alice");
+    let mut alice_settings = settings(ReminderFrequency::OneHour);
+    alice_settings.active_window = window("09:00", "19:00");
+    alice_settings.presence_aware = true;
+    sim.register(&alice, alice_settings).await;
+
+    sim.presence(PresenceEvent::Snapshot(Vec::new())).await;
+    assert!(sim.reminders_until("2026-03-02 18:40").await.is_empty());
+
+    sim.clock.set(time("2026-03-02 20:00"));
+    sim.presence(PresenceEvent::Update(alice.id, OnlineStatus::Online))
+        .await;
+    assert_eq!(
+        sim.reminders_until("2026-03-03 09:30").await,
+        vec![reminded("alice", "2026-03-03 09:00")]
+    );
+}
+
+#[tokio::test]
+async fn users_back_online_get_their_reminder_shortly_after() {
+    let sim = Simulation::start("2026-03-02 08:00").await;
+    let alice = user(1, "alice");
+    let mut alice_settings = settings(ReminderFrequency::OneHour);
+    alice_settings.presence_aware = true;
+    sim.register(&alice, alice_settings).await;
+
+    sim.presence(PresenceEvent::Snapshot(Vec::new())).await;
+    assert!(sim.reminders_until("2026-03-02 09:10").await.is_empty());
+
+    sim.clock.set(time("2026-03-02 09:30"));
+    sim.presence(PresenceEvent::Update(alice.id, OnlineStatus::Online))
+        .await;
+    assert_eq!(
+        sim.reminders_until("2026-03-02 10:40").await,
+        vec![
+            reminded("alice", "2026-03-02 09:32"),
+            reminded("alice", "2026-03-02 10:32"),
+        ]
+    );
+}
+
+/// A user nudged after 45 minutes in voice, reminded every 3 hours otherwise
+fn voice_settings() -> UserSettings {
+    let mut settings = settings(ReminderFrequency::ThreeHours);
+    settings.voice_nudge_minutes = Some(45);
+    settings
+}
+
+/// Voice nudges as (who, when, what), regular reminders left out
+async fn voice_nudges_until(sim: &Simulation, until: &str) -> Vec<(String, NaiveDateTime, String)> {
+    sim.run_until(until)
+        .await
+        .into_iter()
+        .filter(|delivery| delivery.content.contains("en vocal"))
+        .map(|delivery| (delivery.user, delivery.at, delivery.content))
+        .collect()
+}
+
+fn nudged(name: &str, at: &str, time_in_voice: &str) -> (String, NaiveDateTime, String) {
+    (
+        name.to_string(),
+        time(at),
+        format!("🎧 Ça fait {time_in_voice} que tu es en vocal, pense à boire un verre d'eau ! 💧"),
+    )
+}
+
+#[tokio::test]
+async fn voice_nudges_come_after_the_configured_time() {
+    let sim = Simulation::start("2026-03-02 10:00").await;
+    let alice = user(1, "alice");
+    sim.register(&alice, voice_settings()).await;
+
+    sim.voice(&alice, Some(LOUNGE)).await;
+    assert_eq!(voice_nudges_until(&sim, "2026-03-02 10:44").await, vec![]);
+    // Then every 45 minutes while the session goes on, moving between channels included
+    sim.voice(&alice, Some(ChannelId::new(12))).await;
+    assert_eq!(
+        voice_nudges_until(&sim, "2026-03-02 11:30").await,
+        vec![
+            nudged("alice", "2026-03-02 10:45", "45 min"),
+            nudged("alice", "2026-03-02 11:30", "1 h 30"),
+        ]
+    );
+
+    // Members who didn't opt in have no session
+    let bob = user(2, "bob");
+    sim.register(&bob, settings(ReminderFrequency::ThreeHours))
+        .await;
+    sim.voice(&bob, Some(LOUNGE)).await;
+    assert_eq!(lookup_voice_session(&bob).await, None);
+    assert_eq!(
+        voice_nudges_until(&sim, "2026-03-02 12:15").await,
+        vec![nudged("alice", "2026-03-02 12:15", "2 h 15")]
+    );
+}
+
+#[tokio::test]
+async fn voice_sessions_survive_quick_reconnects() {
+    let sim = Simulation::start("2026-03-02 10:00").await;
+    let alice = user(1, "alice");
+    sim.register(&alice, voice_settings()).await;
+    sim.voice(&alice, Some(LOUNGE)).await;
+
+    // A dropped connection within the 3 minutes of grace keeps the session going
+    sim.run_until("2026-03-02 10:20").await;
+    sim.voice(&alice, None).await;
+    sim.run_until("2026-03-02 10:22").await;
+    sim.voice(&alice, Some(LOUNGE)).await;
+    assert_eq!(
+        voice_nudges_until(&sim, "2026-03-02 10:45").await,
+        vec![nudged("alice", "2026-03-02 10:45", "45 min")]
+    );
+
+    // Leaving for longer ends it, the next one starts from scratch
+    sim.run_until("2026-03-02 11:00").await;
+    sim.voice(&alice, None).await;
+    assert_eq!(voice_nudges_until(&sim, "2026-03-02 11:03").await, vec![]);
+    assert_eq!(lookup_voice_session(&alice).await, None);
+    sim.run_until("2026-03-02 11:05").await;
+    sim.voice(&alice, Some(LOUNGE)).await;
+    assert_eq!(
+        voice_nudges_until(&sim, "2026-03-02 11:50").await,
+        vec![nudged("alice", "2026-03-02 11:50", "45 min")]
+    );
+}
+
+#[tokio::test]
+async fn afk_channels_do_not_count_as_voice() {
+    let sim = Simulation::start("2026-03-02 10:00").await;
+    let alice = user(1, "alice");
+    sim.register(&alice, voice_settings()).await;
+
+    // Already in the AFK channel when the guild's voice states come in
+    sync_guild_voice_states(
+        GUILD,
+        Some(AFK),
+        vec![(alice.id, Some(AFK))],
+        sim.clock.now(),
+    )
+    .await;
+    assert_eq!(lookup_voice_session(&alice).await, None);
+    assert_eq!(voice_nudges_until(&sim, "2026-03-02 11:00").await, vec![]);
+
+    sim.voice(&alice, Some(LOUNGE)).await;
+    assert_eq!(
+        voice_nudges_until(&sim, "2026-03-02 11:45").await,
+        vec![nudged("alice", "2026-03-02 11:45", "45 min")]
+    );
+
+    // Moving to the AFK channel counts as leaving
+    sim.run_until("2026-03-02 12:00").await;
+    sim.voice(&alice, Some(AFK)).await;
+    assert_eq!(voice_nudges_until(&sim, "2026-03-02 12:40").await, vec![]);
+    assert_eq!(lookup_voice_session(&alice).await, None);
+}
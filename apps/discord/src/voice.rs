@@ -0,0 +1,327 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use chrono::{NaiveDateTime, TimeDelta};
+use serenity::all::{ChannelId, GuildId, User, UserId};
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+
+use crate::{
+    database,
+    registry::{is_user_on_break, lookup_registered_user, lookup_user_settings},
+    scheduler::{Job, SCHEDULER},
+};
+
+pub const MIN_VOICE_NUDGE_MINUTES: u32 = 15;
+pub const MAX_VOICE_NUDGE_MINUTES: u32 = 4 * 60;
+
+/// Leaving voice for less than this, like a dropped connection, keeps the session going
+pub fn reconnect_grace_period() -> TimeDelta {
+    TimeDelta::minutes(3)
+}
+
+/// Continuous time spent by a user in voice channels, AFK channels excluded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoiceSession {
+    pub started_at: NaiveDateTime,
+    pub last_nudged_at: Option<NaiveDateTime>,
+    /// Set while the user is out of voice, the session ends after the grace period
+    pub left_at: Option<NaiveDateTime>,
+}
+
+impl VoiceSession {
+    fn new(now: NaiveDateTime) -> Self {
+        Self {
+            started_at: now,
+            last_nudged_at: None,
+            left_at: None,
+        }
+    }
+
+    /// When the next nudge is due, or when the session ends once the user left
+    fn next_check(&self, nudge_minutes: u32) -> NaiveDateTime {
+        match self.left_at {
+            Some(left_at) => left_at + reconnect_grace_period(),
+            None => {
+                self.last_nudged_at.unwrap_or(self.started_at)
+                    + TimeDelta::minutes(nudge_minutes as i64)
+            }
+        }
+    }
+
+    fn is_over(&self, now: NaiveDateTime) -> bool {
+        self.left_at
+            .is_some_and(|left_at| now >= left_at + reconnect_grace_period())
+    }
+}
+
+/// Sessions of the users who opted in for voice nudges
+pub static VOICE_SESSIONS: LazyLock<RwLock<HashMap<User, VoiceSession>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Guild and channel of a member in voice
+type VoiceChannel = (Option<GuildId>, ChannelId);
+
+/// Voice channel of every member currently in voice, opted in or not
+static VOICE_CHANNELS: LazyLock<RwLock<HashMap<UserId, VoiceChannel>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+static AFK_CHANNELS: LazyLock<RwLock<HashMap<GuildId, ChannelId>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+async fn is_counted_channel(guild_id: Option<GuildId>, channel_id: ChannelId) -> bool {
+    match guild_id {
+        Some(guild_id) => AFK_CHANNELS.read().await.get(&guild_id) != Some(&channel_id),
+        None => true,
+    }
+}
+
+/// Forgets every voice state kept in memory, as a restart of the bot would
+#[cfg(test)]
+pub async fn clear_voice_states() {
+    VOICE_SESSIONS.write().await.clear();
+    VOICE_CHANNELS.write().await.clear();
+    AFK_CHANNELS.write().await.clear();
+}
+
+pub async fn lookup_voice_session(user: &User) -> Option<VoiceSession> {
+    VOICE_SESSIONS.read().await.get(user).copied()
+}
+
+/// Says "1 h 05" or "45 min"
+pub fn format_voice_time(time: TimeDelta) -> String {
+    let minutes = time.num_minutes();
+    match minutes / 60 {
+        0 => format!("{minutes} min"),
+        hours => format!("{hours} h {:02}", minutes % 60),
+    }
+}
+
+async fn save_session(user: &User, session: &VoiceSession, nudge_minutes: u32) {
+    SCHEDULER.schedule_job(
+        Job::VoiceNudge(user.id),
+        user,
+        Some(session.next_check(nudge_minutes)),
+    );
+
+    // Update database
+    if let Err(e) = database::save_voice_session(user, session).await {
+        error!(
+            "Failed to save voice session of user {} to database: {}",
+            user.name, e
+        );
+    }
+}
+
+pub async fn end_voice_session(user: &User) {
+    SCHEDULER.schedule_job(Job::VoiceNudge(user.id), user, None);
+    if VOICE_SESSIONS.write().await.remove(user).is_none() {
+        return;
+    }
+    info!("Voice session of user {} is over", user.name);
+
+    // Update database
+    if let Err(e) = database::remove_voice_session(user).await {
+        error!(
+            "Failed to remove voice session of user {} from database: {}",
+            user.name, e
+        );
+    }
+}
+
+/// Starts, resumes or leaves the session of an opted-in user
+async fn apply_voice_state(user: &User, in_voice: bool, now: NaiveDateTime) {
+    let Some(nudge_minutes) = lookup_user_settings(user)
+        .await
+        .and_then(|settings| settings.voice_nudge_minutes)
+    else {
+        return;
+    };
+
+    let session = {
+        let mut sessions = VOICE_SESSIONS.write().await;
+        let session = match (sessions.get(user).copied(), in_voice) {
+            // Moving from a channel to another
+            (Some(session), true) if session.left_at.is_none() => return,
+            (Some(session), true) if !session.is_over(now) => {
+                info!("User {} is back in voice, resuming its session", user.name);
+                VoiceSession {
+                    left_at: None,
+                    ..session
+                }
+            }
+            (_, true) => {
+                info!("User {} joined voice, starting its session", user.name);
+                VoiceSession::new(now)
+            }
+            (Some(session), false) if session.left_at.is_none() => {
+                info!("User {} left voice", user.name);
+                VoiceSession {
+                    left_at: Some(now),
+                    ..session
+                }
+            }
+            (_, false) => return,
+        };
+        sessions.insert(user.clone(), session);
+        session
+    };
+    save_session(user, &session, nudge_minutes).await;
+}
+
+/// Handles a voice state update from the gateway, joining an AFK channel counts as leaving
+pub async fn update_voice_state(
+    user_id: UserId,
+    guild_id: Option<GuildId>,
+    channel_id: Option<ChannelId>,
+    now: NaiveDateTime,
+) {
+    let in_voice = match channel_id {
+        Some(channel_id) => {
+            VOICE_CHANNELS
+                .write()
+                .await
+                .insert(user_id, (guild_id, channel_id));
+            is_counted_channel(guild_id, channel_id).await
+        }
+        None => {
+            VOICE_CHANNELS.write().await.remove(&user_id);
+            false
+        }
+    };
+
+    if let Some(user) = lookup_registered_user(user_id).await {
+        apply_voice_state(&user, in_voice, now).await;
+    }
+}
+
+/// Aligns the sessions with the voice states sent with a guild, like after the bot restarted
+pub async fn sync_guild_voice_states(
+    guild_id: GuildId,
+    afk_channel_id: Option<ChannelId>,
+    voice_states: Vec<(UserId, Option<ChannelId>)>,
+    now: NaiveDateTime,
+) {
+    match afk_channel_id {
+        Some(channel_id) => AFK_CHANNELS.write().await.insert(guild_id, channel_id),
+        None => AFK_CHANNELS.write().await.remove(&guild_id),
+    };
+
+    // Users missing from the voice states left while the bot was away
+    let in_guild: Vec<UserId> = voice_states.iter().map(|(user_id, _)| *user_id).collect();
+    let gone: Vec<UserId> = VOICE_CHANNELS
+        .read()
+        .await
+        .iter()
+        .filter(|(user_id, (guild, _))| *guild == Some(guild_id) && !in_guild.contains(user_id))
+        .map(|(user_id, _)| *user_id)
+        .collect();
+    let sessions: Vec<User> = VOICE_SESSIONS.read().await.keys().cloned().collect();
+    for user in sessions {
+        if !in_guild.contains(&user.id) {
+            apply_voice_state(&user, false, now).await;
+        }
+    }
+    for user_id in gone {
+        VOICE_CHANNELS.write().await.remove(&user_id);
+    }
+
+    for (user_id, channel_id) in voice_states {
+        update_voice_state(user_id, Some(guild_id), channel_id, now).await;
+    }
+}
+
+/// Applies a change of voice nudge settings, starting a session right away when the user is
+/// already in voice
+pub async fn refresh_voice_session(user: &User, now: NaiveDateTime) {
+    let Some(nudge_minutes) = lookup_user_settings(user)
+        .await
+        .and_then(|settings| settings.voice_nudge_minutes)
+    else {
+        end_voice_session(user).await;
+        return;
+    };
+
+    if let Some(session) = lookup_voice_session(user).await {
+        save_session(user, &session, nudge_minutes).await;
+        return;
+    }
+    let channel = VOICE_CHANNELS.read().await.get(&user.id).copied();
+    if let Some((guild_id, channel_id)) = channel {
+        let in_voice = is_counted_channel(guild_id, channel_id).await;
+        apply_voice_state(user, in_voice, now).await;
+    }
+}
+
+/// Called when the voice job of a user is due. Ends sessions left for good, and returns the time
+/// spent in voice when a nudge should be sent.
+pub async fn take_due_voice_nudge(user: &User, now: NaiveDateTime) -> Option<TimeDelta> {
+    let Some(nudge_minutes) = lookup_user_settings(user)
+        .await
+        .and_then(|settings| settings.voice_nudge_minutes)
+    else {
+        end_voice_session(user).await;
+        return None;
+    };
+
+    let session = lookup_voice_session(user).await?;
+    if session.is_over(now) {
+        end_voice_session(user).await;
+        return None;
+    }
+    if session.left_at.is_some() || now < session.next_check(nudge_minutes) {
+        save_session(user, &session, nudge_minutes).await;
+        return None;
+    }
+
+    let session = VoiceSession {
+        last_nudged_at: Some(now),
+        ..session
+    };
+    VOICE_SESSIONS.write().await.insert(user.clone(), session);
+    save_session(user, &session, nudge_minutes).await;
+
+    // The session keeps going, but paused users aren't nudged
+    if is_user_on_break(user, now).await {
+        info!("User {} is on a break, skipping its voice nudge", user.name);
+        return None;
+    }
+    Some(now - session.started_at)
+}
+
+/// Sessions of users who left meanwhile end once the guild's voice states come in
+pub async fn load_voice_sessions_from_database() {
+    let sessions = match database::load_voice_sessions().await {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            error!("Failed to load voice sessions from database: {}", e);
+            return;
+        }
+    };
+
+    let mut loaded = 0;
+    for (user_id, session) in sessions.iter() {
+        let Some(user) = lookup_registered_user(UserId::new(*user_id)).await else {
+            warn!("Ignoring voice session of unregistered user {}", user_id);
+            continue;
+        };
+        let Some(nudge_minutes) = lookup_user_settings(&user)
+            .await
+            .and_then(|settings| settings.voice_nudge_minutes)
+        else {
+            continue;
+        };
+
+        VOICE_SESSIONS.write().await.insert(user.clone(), *session);
+        SCHEDULER.schedule_job(
+            Job::VoiceNudge(user.id),
+            &user,
+            Some(session.next_check(nudge_minutes)),
+        );
+        loaded += 1;
+    }
+    info!(
+        "Loaded {}/{} voice sessions from database",
+        loaded,
+        sessions.len()
+    );
+}